@@ -1,12 +1,55 @@
 // sygma_client/src/main.rs - Gerador de Payloads Estruturados (Tier 3)
 
-use tokio::net::TcpStream;
-use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::{TcpStream, UnixStream};
+use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use rand::Rng;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+use sygma_protocol::{code, SygmaError, WireError};
 
 const PROXY_ADDRESS: &str = "127.0.0.1:7878";
+// Outro endereço do Proxy: host:porta ou unix:/caminho (proxy_address do config.yaml)
+const PROXY_ADDRESS_ENV: &str = "SYGMA_PROXY_ADDRESS";
 const VALID_TOKEN_PREFIX: &str = "AUTH_SYGMA_VALID_";
 const INVALID_TOKEN_PREFIX: &str = "FRAUD_ATTEMPT_";
+// Chave HMAC compartilhada com o Proxy (auth.hmac_key). Sem ela, o token vai sem assinatura.
+const HMAC_KEY_ENV: &str = "SYGMA_HMAC_KEY";
+// Modo TLS: CA (PEM) que assina o certificado do Proxy; útil para certificados autoassinados.
+// SYGMA_TLS_SERVER_NAME define o nome verificado no certificado (padrão: localhost).
+const TLS_CA_ENV: &str = "SYGMA_TLS_CA";
+const TLS_SERVER_NAME_ENV: &str = "SYGMA_TLS_SERVER_NAME";
+// mTLS: certificado e chave (PEM) do dispositivo, quando o Proxy exige tls.client_auth
+const TLS_CLIENT_CERT_ENV: &str = "SYGMA_TLS_CLIENT_CERT";
+const TLS_CLIENT_KEY_ENV: &str = "SYGMA_TLS_CLIENT_KEY";
+const SYGMA_ALPN: &[u8] = b"sygma/1";
+// Retentativas (falha de conexão ou erro `retryable`): mesma chave de idempotência em todas,
+// então o Proxy nunca liquida duas vezes
+const MAX_ATTEMPTS: u32 = 3;
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(5);
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+// Assina o token com `;sig=<hex HMAC-SHA256>`, se a chave estiver no ambiente
+fn sign_token(token: &str) -> String {
+    let Ok(key) = std::env::var(HMAC_KEY_ENV) else {
+        return token.to_string();
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC aceita chaves de qualquer tamanho");
+    mac.update(token.as_bytes());
+    format!("{};sig={}", token, hex::encode(mac.finalize().into_bytes()))
+}
+
+// Nonce e timestamp novos por requisição (proteção contra replay do Proxy), antes da assinatura
+fn with_nonce(token: &str) -> String {
+    let nonce: u64 = rand::thread_rng().gen();
+    let ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    format!("{};nonce={:016x};ts={}", token, nonce, ts)
+}
 
 // Geração do Payload ZKP Simulado (O "JSON de Intenção" que o LLM gera)
 fn generate_zkp_payload() -> String {
@@ -19,33 +62,124 @@ fn generate_zkp_payload() -> String {
     format!("ZKP_HASH_S:{}_R:{}_A:{}", sender_id, receiver_id, amount)
 }
 
-// Envio do Comando Estruturado para o Proxy
-async fn send_command(token: &str, payload: &str) -> io::Result<()> {
-    let command = format!("{}|{}", token, payload);
+// Conector TLS que confia apenas no CA informado (sem `SYGMA_TLS_CA`, o cliente usa TCP puro)
+fn tls_connector() -> io::Result<Option<TlsConnector>> {
+    let Ok(ca_path) = std::env::var(TLS_CA_ENV) else {
+        return Ok(None);
+    };
+    let mut reader = std::io::BufReader::new(std::fs::File::open(&ca_path)?);
+    let mut roots = RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut reader) {
+        roots.add(cert?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    }
+
+    let builder = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+        .with_root_certificates(roots);
+    let mut config = match (std::env::var(TLS_CLIENT_CERT_ENV), std::env::var(TLS_CLIENT_KEY_ENV)) {
+        (Ok(cert_path), Ok(key_path)) => {
+            let certs = rustls_pemfile::certs(&mut std::io::BufReader::new(std::fs::File::open(cert_path)?))
+                .collect::<Result<Vec<_>, _>>()?;
+            let key = rustls_pemfile::private_key(&mut std::io::BufReader::new(std::fs::File::open(key_path)?))?
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Nenhuma chave privada PEM"))?;
+            builder.with_client_auth_cert(certs, key)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+        }
+        _ => builder.with_no_client_auth(),
+    };
+    config.alpn_protocols = vec![SYGMA_ALPN.to_vec()];
+    Ok(Some(TlsConnector::from(Arc::new(config))))
+}
+
+// 1. Envio do Comando (uma linha, terminada em `\n`) e 2. Leitura da Resposta do Proxy (TCP ou TLS)
+async fn exchange<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S, command: &str) -> io::Result<String> {
+    stream.write_all(format!("{}\n", command).as_bytes()).await?;
+    let mut response = vec![0; 1024];
+    let n = stream.read(&mut response).await?;
+    Ok(String::from_utf8_lossy(&response[..n]).to_string())
+}
+
+fn proxy_address() -> String {
+    std::env::var(PROXY_ADDRESS_ENV).unwrap_or_else(|_| PROXY_ADDRESS.to_string())
+}
+
+// TLS opcional sobre a conexão já aberta (TCP ou unix)
+async fn exchange_over<S: AsyncRead + AsyncWrite + Unpin>(connector: Option<&TlsConnector>, stream: S, command: &str) -> io::Result<String> {
+    match connector {
+        Some(connector) => {
+            let name = std::env::var(TLS_SERVER_NAME_ENV).unwrap_or_else(|_| "localhost".to_string());
+            let server_name = ServerName::try_from(name)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            exchange(connector.connect(server_name, stream).await?, command).await
+        }
+        None => exchange(stream, command).await,
+    }
+}
+
+// Uma tentativa: conexão (TCP, unix e/ou TLS), envio do comando e leitura da resposta
+async fn attempt(connector: Option<&TlsConnector>, address: &str, command: &str) -> io::Result<String> {
+    match address.strip_prefix("unix:") {
+        Some(path) => exchange_over(connector, UnixStream::connect(path).await?, command).await,
+        None => exchange_over(connector, TcpStream::connect(address).await?, command).await,
+    }
+}
+
+// Envio do Comando Estruturado para o Proxy. Todas as tentativas levam a mesma chave de
+// idempotência: se a primeira chegou ao Kernel, as seguintes recebem a resposta original.
+// `make_token` é chamado a cada tentativa (nonce novo para a proteção contra replay).
+// Decide pelo código do erro (marca `[err=...]`), não pelo texto da resposta.
+async fn send_command(make_token: impl Fn() -> String, payload: &str) -> Result<String, SygmaError> {
+    let idempotency_key = format!("{:032x}", rand::thread_rng().gen::<u128>());
+    let connector = tls_connector()?;
+    let address = proxy_address();
     
-    println!("CLIENT: Tentando conexão com Proxy em {}", PROXY_ADDRESS);
+    println!("CLIENT: Tentando conexão com Proxy em {}{}", address, if connector.is_some() { " (TLS)" } else { "" });
     
-    match TcpStream::connect(PROXY_ADDRESS).await {
-        Ok(mut stream) => {
-            // 1. Envio do Comando
-            stream.write_all(command.as_bytes()).await?;
-            
-            // 2. Leitura da Resposta do Proxy
-            let mut response = vec![0; 1024];
-            let n = stream.read(&mut response).await?;
-            let response_str = String::from_utf8_lossy(&response[..n]);
-            
-            println!("\nCLIENT: Resposta do Proxy:");
-            println!("--------------------------------------------------");
-            println!("{}", response_str.trim());
-            println!("--------------------------------------------------");
+    let mut last_error = SygmaError::Unreachable("nenhuma tentativa".to_string());
+    for attempt_number in 1..=MAX_ATTEMPTS {
+        let command = format!("{}|{}|{}", make_token(), payload, idempotency_key);
+        match tokio::time::timeout(ATTEMPT_TIMEOUT, attempt(connector.as_ref(), &address, &command)).await {
+            // Erro transitório (requisição anterior em andamento, rate limit, Kernel fora do ar...)
+            Ok(Ok(response)) => match WireError::parse(&response) {
+                Some(error) if error.retryable && attempt_number < MAX_ATTEMPTS => {
+                    println!("CLIENT: {}; nova tentativa em {:?}.", error, RETRY_DELAY);
+                }
+                _ => return Ok(response),
+            },
+            Ok(Err(e)) => {
+                eprintln!("\nCLIENT ERROR: Falha na tentativa {}/{}: {}. O Proxy está rodando?", attempt_number, MAX_ATTEMPTS, e);
+                last_error = e.into();
+            }
+            Err(_) => {
+                eprintln!("\nCLIENT ERROR: Tentativa {}/{} sem resposta em {:?}.", attempt_number, MAX_ATTEMPTS, ATTEMPT_TIMEOUT);
+                last_error = SygmaError::Unreachable(format!("sem resposta em {:?}", ATTEMPT_TIMEOUT));
+            }
         }
-        Err(e) => {
-            eprintln!("\nCLIENT ERROR: Falha ao conectar ao Proxy: {}. O Proxy está rodando?", e);
+        tokio::time::sleep(RETRY_DELAY).await;
+    }
+    eprintln!("CLIENT ERROR: Sem resposta após {} tentativas (Idempotency-Key {}).", MAX_ATTEMPTS, idempotency_key);
+    Err(last_error)
+}
+
+// Resposta final de uma transação, com o código estável quando é um erro
+fn report(result: Result<String, SygmaError>) {
+    let response = match result {
+        Ok(response) => response,
+        Err(error) => {
+            eprintln!("CLIENT ERROR: {} (err={}, retryable={})", error, error.code(), error.retryable());
+            return;
         }
+    };
+    println!("\nCLIENT: Resposta do Proxy:");
+    println!("--------------------------------------------------");
+    println!("{}", response.trim());
+    println!("--------------------------------------------------");
+    match WireError::parse(&response) {
+        Some(error) if error.code == code::ZERO_TRUST_VIOLATION => println!("CLIENT: Token recusado pelo Zero-Trust Check (err={}).", error.code),
+        Some(error) => println!("CLIENT: Erro {} (retryable={}).", error.code, error.retryable),
+        None => {}
     }
-    
-    Ok(())
 }
 
 #[tokio::main]
@@ -56,13 +190,13 @@ async fn main() -> io::Result<()> {
     let valid_token = format!("{}{}", VALID_TOKEN_PREFIX, rand::thread_rng().gen::<u64>());
     let valid_payload = generate_zkp_payload();
     println!("\n[TESTE 1: VALIDO] (Token: {})", valid_token);
-    send_command(&valid_token, &valid_payload).await?;
+    report(send_command(|| sign_token(&with_nonce(&valid_token)), &valid_payload).await);
 
     // --- TESTE 2: Transação Inválida/Fraude ---
     let invalid_token = format!("{}{}", INVALID_TOKEN_PREFIX, rand::thread_rng().gen::<u64>());
     let invalid_payload = generate_zkp_payload();
     println!("\n[TESTE 2: FRAUDE] (Token: {})", invalid_token);
-    send_command(&invalid_token, &invalid_payload).await?;
+    report(send_command(|| invalid_token.clone(), &invalid_payload).await);
 
     Ok(())
 }
@@ -0,0 +1,62 @@
+// sygma_kernel/src/listener.rs - Listener do Kernel: host:porta ou unix:/caminho
+//
+// Com SYGMA_KERNEL_ADDRESS=unix:/caminho, o Kernel escuta num Unix domain socket criado com
+// permissão 0600: só o usuário do Kernel (no Termux, o mesmo do Proxy) consegue conectar, e
+// nenhuma porta de loopback fica exposta a outros apps. O Proxy usa o mesmo endereço em
+// `kernel_address` / `kernel_backends`. O arquivo do socket é removido no encerramento.
+
+use std::path::{Path, PathBuf};
+
+use tokio::io::{self, AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, UnixListener};
+
+const UNIX_PREFIX: &str = "unix:";
+const SOCKET_MODE: u32 = 0o600;
+
+// Conexão do Proxy, TCP ou unix
+pub trait Connection: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Connection for T {}
+
+pub enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener, PathBuf),
+}
+
+impl Listener {
+    pub async fn bind(address: &str) -> io::Result<Self> {
+        let Some(path) = address.strip_prefix(UNIX_PREFIX).map(Path::new) else {
+            return Ok(Listener::Tcp(TcpListener::bind(address).await?));
+        };
+        // Socket antigo de um Kernel encerrado sem limpeza: substituído, a menos que ainda responda.
+        // O socket nunca fica com a permissão do umask (ver sygma_protocol::unix_socket).
+        let listener = UnixListener::from_std(sygma_protocol::unix_socket::bind(path, SOCKET_MODE)?)?;
+        Ok(Listener::Unix(listener, path.to_path_buf()))
+    }
+
+    // A conexão e a origem para os logs (endereço TCP ou `unix:uid=<uid>`)
+    pub async fn accept(&self) -> io::Result<(Box<dyn Connection>, String)> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, addr) = listener.accept().await?;
+                stream.set_nodelay(true)?;
+                Ok((Box::new(stream), addr.to_string()))
+            }
+            Listener::Unix(listener, _) => {
+                let (stream, _) = listener.accept().await?;
+                let peer = stream.peer_cred()
+                    .map(|credentials| format!("unix:uid={}", credentials.uid()))
+                    .unwrap_or_else(|_| "unix".to_string());
+                Ok((Box::new(stream), peer))
+            }
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Listener::Unix(_, path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+// sygma_kernel/src/logging.rs - Logs Estruturados do Kernel (T1)
+//
+// SYGMA_KERNEL_LOG: filtro no formato do RUST_LOG (padrão "info")
+// SYGMA_KERNEL_LOG_FORMAT: text | json (padrão text)
+// Cada SETTLE roda num span com o cid recebido do Proxy, então os logs dos dois lados se cruzam.
+
+use std::io::{self, IsTerminal};
+
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter};
+
+const LOG_FILTER_ENV: &str = "SYGMA_KERNEL_LOG";
+const LOG_FORMAT_ENV: &str = "SYGMA_KERNEL_LOG_FORMAT";
+
+// Tamanho máximo aceito para um cid (o Proxy gera ~24 caracteres hexadecimais)
+const MAX_CORRELATION_ID: usize = 64;
+
+pub fn init() -> Result<(), String> {
+    let level = std::env::var(LOG_FILTER_ENV).unwrap_or_else(|_| "info".to_string());
+    let filter = EnvFilter::try_new(&level).map_err(|e| format!("{} inválido '{}': {}", LOG_FILTER_ENV, level, e))?;
+    let registry = tracing_subscriber::registry().with(filter);
+
+    let installed = match std::env::var(LOG_FORMAT_ENV).as_deref() {
+        Ok("json") => registry
+            .with(fmt::layer().json().flatten_event(true).with_current_span(true).with_span_list(false))
+            .try_init(),
+        Ok("text") | Err(_) => registry.with(fmt::layer().with_ansi(io::stdout().is_terminal())).try_init(),
+        Ok(other) => return Err(format!("{} inválido '{}': use text ou json", LOG_FORMAT_ENV, other)),
+    };
+    installed.map_err(|e| format!("Falha ao iniciar os logs: {}", e))
+}
+
+// cid vindo da linha do protocolo; valores fora do formato são ignorados, nunca ecoados
+pub fn correlation_id(value: &str) -> Option<&str> {
+    let valid = !value.is_empty()
+        && value.len() <= MAX_CORRELATION_ID
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then_some(value)
+}
@@ -1,6 +1,24 @@
 // sygna_kernel/src/main.rs
 
 use ark_std::rand::{thread_rng, Rng};
+use tokio::io;
+use tokio::net::TcpListener;
+
+use listener::Listener;
+use tracing::{error, info, warn};
+
+mod listener;
+mod logging;
+mod metrics;
+mod server;
+mod shutdown;
+
+// Endereço onde o Kernel escuta o Proxy (deve bater com `kernel_address` do config.yaml do Proxy):
+// host:porta ou unix:/caminho (ver listener.rs)
+const DEFAULT_KERNEL_ADDRESS: &str = "127.0.0.1:8080";
+const KERNEL_ADDRESS_ENV: &str = "SYGMA_KERNEL_ADDRESS";
+// Endereço do endpoint /metrics (Prometheus); sem a variável, as métricas não são expostas
+const METRICS_ADDRESS_ENV: &str = "SYGMA_KERNEL_METRICS_ADDRESS";
 
 // --- SIMULADOR ZKP: Representa a Prova e a Verificação ---
 
@@ -22,36 +40,83 @@ impl ZKProof {
         }
     }
 
+    pub fn proof_hash(&self) -> &str {
+        &self.proof_hash
+    }
+
     // A função crítica: Verificação da Regra de Ouro (final_balance >= 0)
     pub fn verify(&self) -> bool {
         if self.valid {
-            println!("\n[Sygma Kernel - T1]: Prova criptográfica verificada: VÁLIDA.");
+            info!("Prova criptográfica verificada: VÁLIDA.");
         } else {
-            println!("\n[Sygma Kernel - T1]: Prova criptográfica FALHA. Regra de Ouro violada.");
+            warn!("Prova criptográfica FALHA. Regra de Ouro violada.");
         }
         self.valid
     }
 }
 
+impl Default for ZKProof {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // ----------------------------------------------------------------------
 
-fn main() {
-    println!("--- Sygma Kernel: Zero Core Iniciado (Ambiente Termux/Rust) ---");
+#[tokio::main]
+async fn main() -> io::Result<()> {
+    logging::init().map_err(io::Error::other)?;
+    info!("Sygma Kernel: Zero Core Iniciado (Ambiente Termux/Rust)");
+
+    // Cada SETTLE recebido do Proxy gera uma Prova de Conhecimento Zero e
+    // executa a Liquidação Atômica DENTRO do Kernel
+    let address = std::env::var(KERNEL_ADDRESS_ENV).unwrap_or_else(|_| DEFAULT_KERNEL_ADDRESS.to_string());
+    let listener = Listener::bind(&address).await?;
+    info!("Escutando o Proxy (T2) em {}", address);
 
-    // 1. Simular recebimento de uma Prova de Conhecimento Zero
-    let incoming_proof = ZKProof::new();
+    if let Ok(metrics_address) = std::env::var(METRICS_ADDRESS_ENV) {
+        let metrics_listener = TcpListener::bind(&metrics_address).await?;
+        info!("Métricas em http://{}/metrics", metrics_address);
+        tokio::spawn(async {
+            if let Err(e) = metrics::serve(metrics_listener).await {
+                error!("Endpoint de métricas encerrado: {}", e);
+            }
+        });
+    }
 
-    // 2. Executar a Liquidação Atômica DENTRO do Kernel
-    if execute_atomic_settlement(incoming_proof) {
-        println!("[Sygma Kernel - T1]: Liquidação ATÔMICA concluída. Novo estado comprometido.");
+    // SIGTERM / SIGINT: para de aceitar e espera os Settlements em andamento
+    tokio::spawn(async {
+        match shutdown::wait_for_signal().await {
+            Ok(signal) => {
+                info!("{} recebido. Parando de aceitar requisições do Proxy.", signal);
+                shutdown::DRAIN.stop();
+            }
+            Err(e) => error!("Encerramento gracioso desativado: {}", e),
+        }
+    });
+
+    server::serve(listener).await?;
+
+    let drain_timeout = shutdown::drain_timeout();
+    info!("Aguardando {} conexão(ões) com Settlements em andamento (prazo de {}s).", shutdown::DRAIN.active(), drain_timeout.as_secs());
+    if shutdown::DRAIN.wait_idle(drain_timeout).await {
+        info!("Settlements em andamento concluídos.");
     } else {
-        println!("[Sygma Kernel - T1]: Transação REJEITADA e descartada.");
+        warn!("Prazo de drenagem esgotado com {} conexão(ões) ativa(s).", shutdown::DRAIN.active());
     }
+    info!("Sygma Kernel encerrado.");
+    shutdown::flush();
+    Ok(())
 }
 
 // A Lógica Inevitável: Execução condicionada à Prova.
 fn execute_atomic_settlement(proof: ZKProof) -> bool {
-    if proof.verify() {
+    let timer = metrics::METRICS.verify_duration.start_timer();
+    let valid = proof.verify();
+    timer.observe_duration();
+    metrics::METRICS.verifications.with_label_values(&[if valid { "valid" } else { "invalid" }]).inc();
+
+    if valid {
         // Lógica de update de estado
         true 
     } else {
@@ -0,0 +1,119 @@
+// sygma_kernel/src/metrics.rs - Métricas do Kernel (T1) no formato texto do Prometheus
+//
+// GET /metrics no endereço de SYGMA_KERNEL_METRICS_ADDRESS (ex.: 127.0.0.1:9180).
+
+use std::sync::LazyLock;
+
+use prometheus::{Encoder, Histogram, HistogramOpts, IntCounterVec, Opts, Registry, TextEncoder};
+use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::warn;
+
+// Cabeçalho HTTP lido no máximo (o endpoint só atende GET sem corpo)
+const MAX_REQUEST_HEAD: usize = 4096;
+
+pub struct KernelMetrics {
+    registry: Registry,
+    // Verificações de prova por resultado (valid | invalid)
+    pub verifications: IntCounterVec,
+    pub verify_duration: Histogram,
+    // Settlements por resultado (ok | rejected | error)
+    pub settlements: IntCounterVec,
+    pub settlement_duration: Histogram,
+}
+
+impl KernelMetrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        let verifications = IntCounterVec::new(
+            Opts::new("sygma_kernel_verifications_total", "Verificações de prova ZKP por resultado"),
+            &["result"],
+        ).unwrap();
+        let verify_duration = Histogram::with_opts(
+            HistogramOpts::new("sygma_kernel_verify_duration_seconds", "Tempo de verificação de uma prova ZKP"),
+        ).unwrap();
+        let settlements = IntCounterVec::new(
+            Opts::new("sygma_kernel_settlements_total", "Settlements processados por resultado"),
+            &["result"],
+        ).unwrap();
+        let settlement_duration = Histogram::with_opts(
+            HistogramOpts::new("sygma_kernel_settlement_duration_seconds", "Tempo total de um Settlement (prova + liquidação)"),
+        ).unwrap();
+
+        registry.register(Box::new(verifications.clone())).unwrap();
+        registry.register(Box::new(verify_duration.clone())).unwrap();
+        registry.register(Box::new(settlements.clone())).unwrap();
+        registry.register(Box::new(settlement_duration.clone())).unwrap();
+
+        KernelMetrics { registry, verifications, verify_duration, settlements, settlement_duration }
+    }
+
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+pub static METRICS: LazyLock<KernelMetrics> = LazyLock::new(KernelMetrics::new);
+
+pub async fn serve(listener: TcpListener) -> io::Result<()> {
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        tokio::spawn(async move {
+            if let Err(e) = handle_scrape(stream).await {
+                warn!("Erro ao servir /metrics para {}: {}", addr, e);
+            }
+        });
+    }
+}
+
+// HTTP/1.1 mínimo: uma requisição por conexão
+async fn handle_scrape(mut stream: TcpStream) -> io::Result<()> {
+    let mut head = Vec::new();
+    let mut buffer = [0; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") && head.len() < MAX_REQUEST_HEAD {
+        let n = stream.read(&mut buffer).await?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buffer[..n]);
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let request_line = head.lines().next().unwrap_or_default();
+    let (status, body) = match request_line.split_whitespace().take(2).collect::<Vec<_>>()[..] {
+        ["GET", "/metrics"] => ("200 OK", METRICS.render()),
+        _ => ("404 Not Found", "not found\n".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, body.len(), body
+    );
+    stream.write_all(response.as_bytes()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Teste 1: Um Settlement aparece nos contadores e histogramas expostos em /metrics.
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        crate::server::handle_request("1 SETTLE ZKP_HASH_S:1_R:2_A:3");
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: kernel\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("sygma_kernel_settlements_total"));
+        assert!(response.contains("sygma_kernel_verifications_total"));
+        assert!(response.contains("sygma_kernel_settlement_duration_seconds_count"));
+    }
+}
@@ -0,0 +1,218 @@
+// sygma_kernel/src/server.rs - Protocolo de Linha Multiplexado (Proxy T2 -> Kernel T1)
+//
+// Requisição: "<id> PING" | "<id> [cid=<cid>] SETTLE <payload>"
+// Resposta:   "<id> PONG" | "<id> OK <proof_hash>" | "<id> REJECTED <motivo>" | "<id> ERROR <motivo>"
+// O motivo do ERROR é um `SygmaError` com a marca do código: "<mensagem> [err=5001 retryable=false]".
+// Cada linha é processada em sua própria task, então as respostas podem voltar fora de ordem:
+// o Proxy casa resposta e requisição pelo <id>. O <cid> opcional é o ID de correlação
+// gerado pelo Proxy; só aparece nos logs (span `request`), nunca na resposta.
+// No encerramento (ver shutdown.rs) nenhuma linha nova é lida; as já lidas são respondidas.
+
+use sygma_protocol::SygmaError;
+use tokio::io::{self, AsyncBufRead, AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use tracing::{info, info_span, warn};
+
+use crate::listener::{Connection, Listener};
+use crate::logging;
+use crate::metrics::METRICS;
+use crate::shutdown::DRAIN;
+use crate::{execute_atomic_settlement, ZKProof};
+
+// Respostas aguardando escrita por conexão
+const RESPONSE_QUEUE: usize = 64;
+
+// Termina quando o encerramento começa; as conexões abertas seguem até drenar
+pub async fn serve(listener: Listener) -> io::Result<()> {
+    loop {
+        let (stream, addr) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = DRAIN.stopped() => return Ok(()),
+        };
+        info!("Conexão do Proxy recebida de {}", addr);
+        let in_flight = DRAIN.begin();
+
+        tokio::spawn(async move {
+            let _in_flight = in_flight;
+            if let Err(e) = handle_connection(stream).await {
+                warn!("Erro na conexão com {}: {}", addr, e);
+            }
+        });
+    }
+}
+
+// Conexão persistente (keep-alive): várias requisições em paralelo na mesma conexão
+async fn handle_connection(stream: Box<dyn Connection>) -> io::Result<()> {
+    let (reader, mut writer) = io::split(stream);
+    let (tx, mut rx) = mpsc::channel::<String>(RESPONSE_QUEUE);
+
+    let writer_task = tokio::spawn(async move {
+        while let Some(response) = rx.recv().await {
+            writer.write_all(response.as_bytes()).await?;
+        }
+        Ok::<_, io::Error>(())
+    });
+
+    let mut reader = BufReader::new(reader);
+    let mut buffer = Vec::new();
+    loop {
+        let line = tokio::select! {
+            line = next_line(&mut reader, &mut buffer) => match line? {
+                Some(line) => line,
+                None => break,
+            },
+            _ = DRAIN.stopped() => break,
+        };
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            let _ = tx.send(handle_request(&line)).await;
+        });
+    }
+
+    // O escritor termina depois da última resposta pendente
+    drop(tx);
+    writer_task.await.map_err(io::Error::other)?
+}
+
+// Próxima linha terminada em `\n`. Sem `\n` no fim da conexão, a linha foi cortada
+// (o Proxy desistiu no meio do envio) e nunca é executada.
+// `buffer` guarda o que já foi lido se o `select!` cancelar a leitura.
+async fn next_line<R: AsyncBufRead + Unpin>(reader: &mut R, buffer: &mut Vec<u8>) -> io::Result<Option<String>> {
+    reader.read_until(b'\n', buffer).await?;
+    if buffer.last() != Some(&b'\n') {
+        if !buffer.is_empty() {
+            warn!("Linha incompleta ({} bytes) descartada no fim da conexão.", buffer.len());
+        }
+        return Ok(None);
+    }
+    String::from_utf8(std::mem::take(buffer))
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn handle_request(line: &str) -> String {
+    let (id, command) = line.trim_end().split_once(' ').unwrap_or((line.trim_end(), ""));
+    let (cid, command) = match command.strip_prefix("cid=").and_then(|rest| rest.split_once(' ')) {
+        Some((cid, rest)) => (logging::correlation_id(cid), rest),
+        None => (None, command),
+    };
+    let (verb, argument) = command.split_once(' ').unwrap_or((command, ""));
+    let _span = info_span!("request", cid = cid.unwrap_or("-")).entered();
+
+    let body = match verb {
+        "PING" => "PONG".to_string(),
+        "SETTLE" if !argument.is_empty() => settle(argument),
+        "SETTLE" => {
+            METRICS.settlements.with_label_values(&["error"]).inc();
+            format!("ERROR {}", SygmaError::EmptyPayload.tagged())
+        }
+        _ => format!("ERROR {}", SygmaError::UnknownCommand(verb.to_string()).tagged()),
+    };
+    format!("{} {}\n", id, body)
+}
+
+fn settle(payload: &str) -> String {
+    info!("Settlement solicitado para {}", payload);
+    let _timer = METRICS.settlement_duration.start_timer();
+    let proof = ZKProof::new();
+    let proof_hash = proof.proof_hash().to_string();
+
+    if execute_atomic_settlement(proof) {
+        METRICS.settlements.with_label_values(&["ok"]).inc();
+        info!("Liquidação ATÔMICA concluída. Novo estado comprometido.");
+        format!("OK {}", proof_hash)
+    } else {
+        METRICS.settlements.with_label_values(&["rejected"]).inc();
+        warn!("Transação REJEITADA e descartada.");
+        "REJECTED Regra de Ouro violada".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::{TcpStream, UnixStream};
+
+    // Teste 1: PING responde PONG com o mesmo id.
+    #[test]
+    fn test_ping() {
+        assert_eq!(handle_request("7 PING\n"), "7 PONG\n");
+    }
+
+    // Teste 2: SETTLE sempre devolve um veredito (OK ou REJECTED) para o id da requisição.
+    #[test]
+    fn test_settle_returns_verdict() {
+        let response = handle_request("42 SETTLE ZKP_HASH_S:1_R:2_A:300");
+        assert!(response.starts_with("42 OK ZKP_COMMITMENT_") || response == "42 REJECTED Regra de Ouro violada\n");
+    }
+
+    // Teste 3: Comandos inválidos são respondidos com ERROR e código estável, sem derrubar a conexão.
+    #[test]
+    fn test_unknown_command() {
+        assert!(handle_request("3 DROP TABLE").starts_with("3 ERROR"));
+        assert!(handle_request("4 SETTLE").starts_with("4 ERROR"));
+        let error = sygma_protocol::WireError::parse(&handle_request("5 DROP TABLE")).unwrap();
+        assert_eq!((error.code, error.retryable), (sygma_protocol::code::UNKNOWN_COMMAND, false));
+    }
+
+    // Teste 4: O cid do Proxy é aceito antes do comando e não altera a resposta.
+    #[test]
+    fn test_correlation_id_prefix() {
+        assert_eq!(handle_request("8 cid=18c2f9a01b2c0007 PING\n"), "8 PONG\n");
+        let response = handle_request("9 cid=18c2f9a01b2c0008 SETTLE ZKP_HASH_S:1_R:2_A:300");
+        assert!(response.starts_with("9 OK ") || response.starts_with("9 REJECTED "));
+        assert_eq!(logging::correlation_id("abc def"), None);
+        assert_eq!(handle_request("10 cid=x{y} PING"), "10 PONG\n", "cid inválido é ignorado.");
+    }
+
+    // Teste 5: No encerramento o Kernel para de aceitar e fecha as conexões depois de responder.
+    #[tokio::test]
+    async fn test_graceful_shutdown() {
+        let listener = Listener::bind("127.0.0.1:0").await.unwrap();
+        let Listener::Tcp(tcp) = &listener else { unreachable!() };
+        let addr = tcp.local_addr().unwrap();
+        let server = tokio::spawn(serve(listener));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"1 PING\n").await.unwrap();
+        let mut response = [0; 7];
+        stream.read_exact(&mut response).await.unwrap();
+        assert_eq!(&response, b"1 PONG\n");
+
+        DRAIN.stop();
+        server.await.unwrap().unwrap();
+        assert!(DRAIN.wait_idle(std::time::Duration::from_secs(1)).await, "A conexão ociosa deve ser drenada.");
+        assert_eq!(stream.read(&mut response).await.unwrap(), 0, "O Kernel fecha a conexão.");
+    }
+
+    // Teste 6: Em unix:/caminho o socket é criado com permissão 0600 e removido no encerramento.
+    #[tokio::test]
+    async fn test_unix_socket_listener() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("sygma_kernel_test_{}.sock", std::process::id()));
+        let listener = Listener::bind(&format!("unix:{}", path.display())).await.unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        let mut client = UnixStream::connect(&path).await.unwrap();
+        let (mut stream, peer) = listener.accept().await.unwrap();
+        assert!(peer.starts_with("unix:uid="));
+        client.write_all(b"5 PING\n").await.unwrap();
+        let mut request = [0; 7];
+        stream.read_exact(&mut request).await.unwrap();
+        assert_eq!(handle_request(std::str::from_utf8(&request).unwrap()), "5 PONG\n");
+
+        drop(listener);
+        assert!(!path.exists(), "O arquivo do socket é removido com o listener.");
+    }
+
+    // Teste 7: Uma linha sem `\n` no fim da conexão (envio interrompido) não é executada.
+    #[tokio::test]
+    async fn test_truncated_line_ignored() {
+        let mut reader: &[u8] = b"1 PING\n2 SETTLE ZKP_HASH_S:1_R:2_A:3";
+        let mut buffer = Vec::new();
+        assert_eq!(next_line(&mut reader, &mut buffer).await.unwrap().as_deref(), Some("1 PING\n"));
+        assert_eq!(next_line(&mut reader, &mut buffer).await.unwrap(), None);
+    }
+}
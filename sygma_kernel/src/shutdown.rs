@@ -0,0 +1,84 @@
+// sygma_kernel/src/shutdown.rs - Encerramento Gracioso do Kernel (SIGTERM / SIGINT)
+//
+// Com o sinal, o Kernel para de aceitar conexões e de ler novas linhas do Proxy; os Settlements
+// já recebidos terminam e têm a resposta escrita antes da saída, até
+// SYGMA_KERNEL_DRAIN_TIMEOUT_SECS (padrão 30s). Um Settlement nunca é cortado ao meio por um
+// encerramento dentro do prazo.
+
+use std::io::Write;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use tokio::io;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+
+const DRAIN_TIMEOUT_ENV: &str = "SYGMA_KERNEL_DRAIN_TIMEOUT_SECS";
+const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Sinal de parada + contagem de conexões com trabalho em andamento
+pub struct Drain {
+    stopping: watch::Sender<bool>,
+    active: watch::Sender<usize>,
+}
+
+pub struct InFlight(&'static Drain);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.active.send_modify(|active| *active -= 1);
+    }
+}
+
+impl Drain {
+    fn new() -> Self {
+        Drain { stopping: watch::Sender::new(false), active: watch::Sender::new(0) }
+    }
+
+    pub fn begin(&'static self) -> InFlight {
+        self.active.send_modify(|active| *active += 1);
+        InFlight(self)
+    }
+
+    pub fn active(&self) -> usize {
+        *self.active.borrow()
+    }
+
+    pub fn stop(&self) {
+        self.stopping.send_replace(true);
+    }
+
+    pub async fn stopped(&self) {
+        let _ = self.stopping.subscribe().wait_for(|stopping| *stopping).await;
+    }
+
+    // `false` se o prazo acabou com trabalho ainda em andamento
+    pub async fn wait_idle(&self, timeout: Duration) -> bool {
+        let mut active = self.active.subscribe();
+        let drained = tokio::time::timeout(timeout, active.wait_for(|active| *active == 0)).await;
+        drained.is_ok()
+    }
+}
+
+pub static DRAIN: LazyLock<Drain> = LazyLock::new(Drain::new);
+
+pub fn drain_timeout() -> Duration {
+    std::env::var(DRAIN_TIMEOUT_ENV).ok()
+        .and_then(|secs| secs.parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_DRAIN_TIMEOUT)
+}
+
+pub async fn wait_for_signal() -> io::Result<&'static str> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    Ok(tokio::select! {
+        _ = terminate.recv() => "SIGTERM",
+        _ = interrupt.recv() => "SIGINT",
+    })
+}
+
+pub fn flush() {
+    let _ = std::io::stdout().flush();
+    let _ = std::io::stderr().flush();
+}
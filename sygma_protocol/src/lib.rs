@@ -0,0 +1,340 @@
+// sygma_protocol/src/lib.rs - Erros do Protocolo Sygma (compartilhados por Client, Proxy e Kernel)
+//
+// Cada erro tem um código numérico estável, o status HTTP, a indicação `retryable` (a mesma
+// requisição pode dar certo mais tarde) e uma mensagem legível. No fio, a linha de resposta
+// termina com a marca `[err=<código> retryable=<true|false>]`:
+//
+//   403 ACCESS DENIED: Zero Trust Violation [err=2001 retryable=false]
+//   7 ERROR BAD REQUEST: Empty payload [err=5001 retryable=false]     (Kernel -> Proxy)
+//
+// Na API HTTP, os mesmos campos vão no corpo JSON (`error_code`, `retryable`). Clientes decidem
+// pelo código (`WireError::parse`), nunca pelo texto. Códigos não são reaproveitados: um erro
+// novo recebe um código novo na sua faixa.
+//   1xxx requisição malformada   2xxx acesso e autenticação   3xxx limites e política
+//   4xxx Settlement e Kernel     5xxx protocolo do Kernel     9xxx falhas internas
+
+use std::fmt;
+use std::io;
+
+// Bind dos sockets `unix:/caminho` do Proxy e do Kernel
+pub mod unix_socket;
+
+// Códigos estáveis do protocolo
+pub mod code {
+    pub const INVALID_REQUEST: u16 = 1001;
+    pub const INVALID_PAYLOAD: u16 = 1002;
+    pub const INVALID_IDEMPOTENCY_KEY: u16 = 1003;
+    pub const INVALID_JSON: u16 = 1004;
+    pub const PAYLOAD_TOO_LARGE: u16 = 1005;
+    pub const REQUEST_TIMEOUT: u16 = 1006;
+    pub const UNKNOWN_ROUTE: u16 = 1007;
+    pub const METHOD_NOT_ALLOWED: u16 = 1008;
+    pub const SETTLEMENT_NOT_FOUND: u16 = 1009;
+    pub const TICKET_NOT_FOUND: u16 = 1010;
+
+    pub const ZERO_TRUST_VIOLATION: u16 = 2001;
+    pub const PEER_NOT_ALLOWED: u16 = 2002;
+    pub const CERTIFICATE_REJECTED: u16 = 2003;
+    pub const NONCE_REQUIRED: u16 = 2004;
+    pub const STALE_TIMESTAMP: u16 = 2005;
+    pub const REPLAY_DETECTED: u16 = 2006;
+    pub const LOCKED_OUT: u16 = 2007;
+
+    pub const RATE_LIMITED: u16 = 3001;
+    pub const POLICY_DENIED: u16 = 3002;
+    pub const IDEMPOTENCY_IN_PROGRESS: u16 = 3003;
+    pub const IDEMPOTENCY_KEY_REUSED: u16 = 3004;
+
+    pub const SETTLEMENT_REJECTED: u16 = 4001;
+    pub const KERNEL_UNAVAILABLE: u16 = 4002;
+    pub const QUEUE_FULL: u16 = 4003;
+    pub const KERNEL_FAILED: u16 = 4004;
+    pub const KERNEL_BAD_RESPONSE: u16 = 4005;
+
+    pub const EMPTY_PAYLOAD: u16 = 5001;
+    pub const UNKNOWN_COMMAND: u16 = 5002;
+
+    pub const INTERNAL: u16 = 9001;
+    pub const UNREACHABLE: u16 = 9002;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SygmaError {
+    // Requisição TCP fora do formato `token|payload[|chave]`
+    InvalidRequest,
+    // Motivo preciso (campo e problema)
+    InvalidPayload(String),
+    InvalidIdempotencyKey,
+    InvalidJson(String),
+    // Limite do listener que recusou a requisição (corpo HTTP ou linha TCP)
+    PayloadTooLarge { limit_bytes: usize },
+    RequestTimeout,
+    UnknownRoute,
+    MethodNotAllowed,
+    SettlementNotFound,
+    TicketNotFound,
+
+    ZeroTrustViolation,
+    PeerNotAllowed,
+    CertificateRejected(String),
+    NonceRequired,
+    StaleTimestamp,
+    ReplayDetected,
+    LockedOut { retry_after_secs: u64 },
+
+    RateLimited { retry_after_secs: u64 },
+    PolicyDenied(String),
+    IdempotencyInProgress,
+    IdempotencyKeyReused,
+
+    // Veredito do Kernel
+    SettlementRejected(String),
+    // `retry_after_secs` quando o circuit breaker está aberto
+    KernelUnavailable { retry_after_secs: Option<u64> },
+    QueueFull,
+    // O SETTLE chegou ao Kernel e o resultado é incerto: nunca reenviar
+    KernelFailed,
+    KernelBadResponse,
+
+    EmptyPayload,
+    UnknownCommand(String),
+
+    Internal(String),
+    // Nenhuma resposta do outro lado (conexão recusada, tempo esgotado)
+    Unreachable(String),
+}
+
+impl SygmaError {
+    pub fn code(&self) -> u16 {
+        match self {
+            SygmaError::InvalidRequest => code::INVALID_REQUEST,
+            SygmaError::InvalidPayload(_) => code::INVALID_PAYLOAD,
+            SygmaError::InvalidIdempotencyKey => code::INVALID_IDEMPOTENCY_KEY,
+            SygmaError::InvalidJson(_) => code::INVALID_JSON,
+            SygmaError::PayloadTooLarge { .. } => code::PAYLOAD_TOO_LARGE,
+            SygmaError::RequestTimeout => code::REQUEST_TIMEOUT,
+            SygmaError::UnknownRoute => code::UNKNOWN_ROUTE,
+            SygmaError::MethodNotAllowed => code::METHOD_NOT_ALLOWED,
+            SygmaError::SettlementNotFound => code::SETTLEMENT_NOT_FOUND,
+            SygmaError::TicketNotFound => code::TICKET_NOT_FOUND,
+            SygmaError::ZeroTrustViolation => code::ZERO_TRUST_VIOLATION,
+            SygmaError::PeerNotAllowed => code::PEER_NOT_ALLOWED,
+            SygmaError::CertificateRejected(_) => code::CERTIFICATE_REJECTED,
+            SygmaError::NonceRequired => code::NONCE_REQUIRED,
+            SygmaError::StaleTimestamp => code::STALE_TIMESTAMP,
+            SygmaError::ReplayDetected => code::REPLAY_DETECTED,
+            SygmaError::LockedOut { .. } => code::LOCKED_OUT,
+            SygmaError::RateLimited { .. } => code::RATE_LIMITED,
+            SygmaError::PolicyDenied(_) => code::POLICY_DENIED,
+            SygmaError::IdempotencyInProgress => code::IDEMPOTENCY_IN_PROGRESS,
+            SygmaError::IdempotencyKeyReused => code::IDEMPOTENCY_KEY_REUSED,
+            SygmaError::SettlementRejected(_) => code::SETTLEMENT_REJECTED,
+            SygmaError::KernelUnavailable { .. } => code::KERNEL_UNAVAILABLE,
+            SygmaError::QueueFull => code::QUEUE_FULL,
+            SygmaError::KernelFailed => code::KERNEL_FAILED,
+            SygmaError::KernelBadResponse => code::KERNEL_BAD_RESPONSE,
+            SygmaError::EmptyPayload => code::EMPTY_PAYLOAD,
+            SygmaError::UnknownCommand(_) => code::UNKNOWN_COMMAND,
+            SygmaError::Internal(_) => code::INTERNAL,
+            SygmaError::Unreachable(_) => code::UNREACHABLE,
+        }
+    }
+
+    // Status HTTP (também o início da linha do protocolo TCP)
+    pub fn status(&self) -> u16 {
+        match self {
+            SygmaError::InvalidRequest
+            | SygmaError::InvalidPayload(_)
+            | SygmaError::InvalidIdempotencyKey
+            | SygmaError::InvalidJson(_)
+            | SygmaError::EmptyPayload
+            | SygmaError::UnknownCommand(_) => 400,
+            SygmaError::NonceRequired | SygmaError::StaleTimestamp => 401,
+            SygmaError::ZeroTrustViolation
+            | SygmaError::PeerNotAllowed
+            | SygmaError::CertificateRejected(_)
+            | SygmaError::PolicyDenied(_) => 403,
+            SygmaError::UnknownRoute | SygmaError::SettlementNotFound | SygmaError::TicketNotFound => 404,
+            SygmaError::MethodNotAllowed => 405,
+            SygmaError::RequestTimeout => 408,
+            SygmaError::ReplayDetected | SygmaError::IdempotencyInProgress => 409,
+            SygmaError::PayloadTooLarge { .. } => 413,
+            SygmaError::IdempotencyKeyReused | SygmaError::SettlementRejected(_) => 422,
+            SygmaError::LockedOut { .. } | SygmaError::RateLimited { .. } => 429,
+            SygmaError::Internal(_) => 500,
+            SygmaError::KernelFailed | SygmaError::KernelBadResponse => 502,
+            SygmaError::KernelUnavailable { .. } | SygmaError::QueueFull | SygmaError::Unreachable(_) => 503,
+        }
+    }
+
+    // A mesma requisição (com a mesma chave de idempotência) pode ser repetida mais tarde
+    pub fn retryable(&self) -> bool {
+        matches!(
+            self,
+            SygmaError::RequestTimeout
+                | SygmaError::LockedOut { .. }
+                | SygmaError::RateLimited { .. }
+                | SygmaError::IdempotencyInProgress
+                | SygmaError::KernelUnavailable { .. }
+                | SygmaError::QueueFull
+                | SygmaError::Internal(_)
+                | SygmaError::Unreachable(_)
+        )
+    }
+
+    // Linha completa do protocolo: "<status> <mensagem> [err=<código> retryable=<bool>]"
+    pub fn response(&self) -> String {
+        format!("{} {}", self.status(), self.tagged())
+    }
+
+    // Mensagem seguida da marca, sem o status (linha `ERROR` do Kernel)
+    pub fn tagged(&self) -> String {
+        format!("{} [err={} retryable={}]", self, self.code(), self.retryable())
+    }
+}
+
+// Mensagem legível, sem status nem código
+impl fmt::Display for SygmaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SygmaError::InvalidRequest => write!(f, "ERROR: Invalid Sygma Request Format"),
+            SygmaError::InvalidPayload(reason) => write!(f, "INVALID PAYLOAD: {}", reason),
+            SygmaError::InvalidIdempotencyKey => write!(f, "ERROR: Invalid Idempotency Key"),
+            SygmaError::InvalidJson(reason) => write!(f, "ERROR: Invalid JSON body ({})", reason),
+            SygmaError::PayloadTooLarge { limit_bytes } => write!(f, "PAYLOAD TOO LARGE: Request exceeds {} bytes", limit_bytes),
+            SygmaError::RequestTimeout => write!(f, "REQUEST TIMEOUT: Request body not received in time"),
+            SygmaError::UnknownRoute => write!(f, "NOT FOUND: Unknown route"),
+            SygmaError::MethodNotAllowed => write!(f, "METHOD NOT ALLOWED"),
+            SygmaError::SettlementNotFound => write!(f, "NOT FOUND: Settlement not found"),
+            SygmaError::TicketNotFound => write!(f, "NOT FOUND: Ticket not found"),
+            SygmaError::ZeroTrustViolation => write!(f, "ACCESS DENIED: Zero Trust Violation"),
+            SygmaError::PeerNotAllowed => write!(f, "ACCESS DENIED: Peer UID Not Allowed"),
+            SygmaError::CertificateRejected(reason) => write!(f, "ACCESS DENIED: {}", reason),
+            SygmaError::NonceRequired => write!(f, "REPLAY PROTECTION: Nonce and timestamp required"),
+            SygmaError::StaleTimestamp => write!(f, "REPLAY PROTECTION: Stale Timestamp"),
+            SygmaError::ReplayDetected => write!(f, "REPLAY DETECTED: Nonce already used"),
+            SygmaError::LockedOut { retry_after_secs } => write!(f, "TOO MANY REQUESTS: Source Locked Out (retry after {}s)", retry_after_secs),
+            SygmaError::RateLimited { retry_after_secs } => write!(f, "RATE LIMITED: Too Many Requests (Retry-After: {}s)", retry_after_secs),
+            SygmaError::PolicyDenied(reason) => write!(f, "POLICY DENIED: {}", reason),
+            SygmaError::IdempotencyInProgress => write!(f, "CONFLICT: Request with this Idempotency Key is still in progress (Retry-After: 1s)"),
+            SygmaError::IdempotencyKeyReused => write!(f, "IDEMPOTENCY KEY REUSED: Different payload for the same key"),
+            SygmaError::SettlementRejected(reason) => write!(f, "SETTLEMENT REJECTED: {}", reason),
+            SygmaError::KernelUnavailable { retry_after_secs: Some(seconds) } => {
+                write!(f, "SERVICE UNAVAILABLE: Kernel T1 Offline (Circuit Open, Retry-After: {}s)", seconds)
+            }
+            SygmaError::KernelUnavailable { retry_after_secs: None } => write!(f, "SERVICE UNAVAILABLE: Kernel T1 Offline"),
+            SygmaError::QueueFull => write!(f, "SERVICE UNAVAILABLE: Store-and-Forward Queue Full"),
+            SygmaError::KernelFailed => write!(f, "BAD GATEWAY: Kernel T1 falhou durante o Settlement"),
+            SygmaError::KernelBadResponse => write!(f, "BAD GATEWAY: Resposta inesperada do Kernel T1"),
+            SygmaError::EmptyPayload => write!(f, "BAD REQUEST: Empty payload"),
+            SygmaError::UnknownCommand(verb) => write!(f, "BAD REQUEST: Unknown command {}", verb),
+            SygmaError::Internal(reason) => write!(f, "INTERNAL ERROR: {}", reason),
+            SygmaError::Unreachable(reason) => write!(f, "SERVICE UNAVAILABLE: No response ({})", reason),
+        }
+    }
+}
+
+impl std::error::Error for SygmaError {}
+
+impl From<io::Error> for SygmaError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::ConnectionRefused | io::ErrorKind::NotFound | io::ErrorKind::TimedOut => SygmaError::Unreachable(error.to_string()),
+            _ => SygmaError::Internal(error.to_string()),
+        }
+    }
+}
+
+impl From<SygmaError> for io::Error {
+    fn from(error: SygmaError) -> Self {
+        io::Error::other(error)
+    }
+}
+
+// Erro lido do fio: o que o cliente precisa para decidir sem interpretar o texto
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WireError {
+    // Status da linha (0 na linha `ERROR` do Kernel, que não tem status)
+    pub status: u16,
+    pub code: u16,
+    pub retryable: bool,
+    pub message: String,
+}
+
+impl WireError {
+    // `None` para respostas de sucesso (mesmo as que citam um erro, como a consulta de um ticket
+    // rejeitado) ou sem a marca `[err=...]` (versões antigas)
+    pub fn parse(line: &str) -> Option<WireError> {
+        let start = line.rfind(" [err=")?;
+        let (body, tag) = (&line[..start], &line[start + " [err=".len()..]);
+        let (tag, _) = tag.split_once(']')?;
+        let (code, retryable) = tag.split_once(" retryable=")?;
+        let (status, message) = match body.split_once(' ') {
+            Some((status, message)) if status.len() == 3 && status.bytes().all(|b| b.is_ascii_digit()) => (status.parse().ok()?, message),
+            _ => (0, body),
+        };
+        if (1..400).contains(&status) {
+            return None;
+        }
+        Some(WireError { status, code: code.parse().ok()?, retryable: retryable.parse().ok()?, message: message.to_string() })
+    }
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (err={})", self.message, self.code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Teste 1: Linha do fio com status, mensagem e marca; a marca sobrevive a sufixos como [cid=...].
+    #[test]
+    fn test_wire_roundtrip() {
+        let error = SygmaError::ZeroTrustViolation;
+        assert_eq!(error.response(), "403 ACCESS DENIED: Zero Trust Violation [err=2001 retryable=false]");
+        let parsed = WireError::parse(&format!("{} [cid=1a2b]", error.response())).unwrap();
+        assert_eq!(parsed, WireError { status: 403, code: code::ZERO_TRUST_VIOLATION, retryable: false, message: "ACCESS DENIED: Zero Trust Violation".to_string() });
+
+        let unavailable = SygmaError::KernelUnavailable { retry_after_secs: Some(7) };
+        let parsed = WireError::parse(&unavailable.response()).unwrap();
+        assert_eq!((parsed.status, parsed.code, parsed.retryable), (503, code::KERNEL_UNAVAILABLE, true));
+        assert!(parsed.message.ends_with("(Circuit Open, Retry-After: 7s)"));
+
+        let kernel = WireError::parse(&format!("7 ERROR {}", SygmaError::EmptyPayload.tagged())).unwrap();
+        assert_eq!((kernel.status, kernel.code), (0, code::EMPTY_PAYLOAD));
+        assert_eq!(WireError::parse("200 OK: Payload liquidado [cid=1a2b]"), None);
+        let lookup = format!("200 OK: Ticket tkt_1 rejected -> {}", SygmaError::SettlementRejected("Regra de Ouro violada".to_string()).response());
+        assert_eq!(WireError::parse(&lookup), None);
+        assert_eq!(WireError::parse("403 ACCESS DENIED: Zero Trust Violation"), None);
+    }
+
+    // Teste 2: Códigos únicos, status coerente e só falhas transitórias marcadas como retryable.
+    #[test]
+    fn test_codes_and_retryable() {
+        let all = [
+            SygmaError::InvalidRequest, SygmaError::InvalidPayload(String::new()), SygmaError::InvalidIdempotencyKey,
+            SygmaError::InvalidJson(String::new()), SygmaError::PayloadTooLarge { limit_bytes: 1 }, SygmaError::RequestTimeout,
+            SygmaError::UnknownRoute, SygmaError::MethodNotAllowed, SygmaError::SettlementNotFound, SygmaError::TicketNotFound,
+            SygmaError::ZeroTrustViolation, SygmaError::PeerNotAllowed, SygmaError::CertificateRejected(String::new()),
+            SygmaError::NonceRequired, SygmaError::StaleTimestamp, SygmaError::ReplayDetected, SygmaError::LockedOut { retry_after_secs: 1 },
+            SygmaError::RateLimited { retry_after_secs: 1 }, SygmaError::PolicyDenied(String::new()), SygmaError::IdempotencyInProgress,
+            SygmaError::IdempotencyKeyReused, SygmaError::SettlementRejected(String::new()), SygmaError::KernelUnavailable { retry_after_secs: None },
+            SygmaError::QueueFull, SygmaError::KernelFailed, SygmaError::KernelBadResponse, SygmaError::EmptyPayload,
+            SygmaError::UnknownCommand(String::new()), SygmaError::Internal(String::new()), SygmaError::Unreachable(String::new()),
+        ];
+        let mut codes: Vec<u16> = all.iter().map(SygmaError::code).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), all.len(), "Cada erro tem o seu código.");
+        assert!(all.iter().all(|error| (400..600).contains(&error.status())));
+
+        assert!(SygmaError::RateLimited { retry_after_secs: 2 }.retryable());
+        assert!(SygmaError::IdempotencyInProgress.retryable());
+        assert!(!SygmaError::KernelFailed.retryable(), "Resultado incerto: reenviar poderia liquidar duas vezes.");
+        assert!(!SygmaError::PolicyDenied("amount".to_string()).retryable());
+        assert_eq!(SygmaError::from(io::Error::from(io::ErrorKind::ConnectionRefused)).code(), code::UNREACHABLE);
+    }
+}
@@ -0,0 +1,72 @@
+// sygma_protocol/src/unix_socket.rs - Bind de Unix domain sockets (Proxy e Kernel)
+//
+// O socket nunca fica visível com a permissão do umask: o bind é feito num diretório 0700 ao lado
+// do destino e o arquivo só aparece no caminho final (rename) já com o `mode` pedido.
+
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+
+// Um arquivo de socket antigo (processo encerrado sem limpeza) é substituído; se outro processo
+// ainda responde nele, o bind falha. O listener volta em modo não bloqueante, pronto para
+// `tokio::net::UnixListener::from_std`.
+pub fn bind(path: &Path, mode: u32) -> io::Result<UnixListener> {
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} existe e não é um socket", path.display())));
+        }
+        if UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(io::ErrorKind::AddrInUse, format!("{} já está em uso por outro processo", path.display())));
+        }
+        std::fs::remove_file(path)?;
+    }
+
+    let staging = staging_dir(path);
+    // Sobra de um bind interrompido (queda entre o mkdir e a limpeza). A de outro usuário não
+    // pode ser removida, e o bind falha com o erro da remoção.
+    match std::fs::symlink_metadata(&staging) {
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(&staging)?,
+        Ok(_) => std::fs::remove_file(&staging)?,
+        Err(_) => {}
+    }
+    std::fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let staged = staging.join("sock");
+    let bound = UnixListener::bind(&staged).and_then(|listener| {
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(mode))?;
+        std::fs::rename(&staged, path)?;
+        listener.set_nonblocking(true)?;
+        Ok(listener)
+    });
+    let _ = std::fs::remove_dir_all(&staging);
+    bound
+}
+
+// `/dir/proxy.sock` -> `/dir/.proxy.sock.bind`
+pub fn staging_dir(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.bind", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Teste 1: O socket aparece já com o `mode`; uma sobra de bind interrompido não impede o próximo.
+    #[test]
+    fn test_bind_replaces_stale_staging_dir() {
+        let path = std::env::temp_dir().join(format!("sygma_protocol_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        std::fs::DirBuilder::new().mode(0o700).create(staging_dir(&path)).unwrap();
+        std::fs::write(staging_dir(&path).join("sock"), b"").unwrap();
+
+        let listener = bind(&path, 0o600).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+        assert!(!staging_dir(&path).exists(), "O diretório do bind é removido.");
+        assert_eq!(bind(&path, 0o600).unwrap_err().kind(), io::ErrorKind::AddrInUse);
+
+        drop(listener);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
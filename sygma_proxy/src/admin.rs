@@ -0,0 +1,170 @@
+// sygma_proxy/src/admin.rs - Socket de Administração (Unix domain socket local)
+//
+// Um comando por conexão, em uma linha; a resposta começa com `OK` ou `ERR <motivo>` e a
+// conexão é fechada ao final. O socket é criado com permissão 0600 (ver unix_socket.rs): só o
+// usuário do Proxy (e o root) conseguem conectar. Cliente: `sygma-admin` (src/bin/sygma-admin.rs).
+//
+//   cache stats                  entradas, capacidade, hits e misses do TRUST_CACHE
+//   cache list                   entradas do TRUST_CACHE (tokens sempre redigidos)
+//   cache invalidate <token>     o token em claro ou a forma redigida mostrada por `cache list`
+//   cache invalidate-all
+//   backends                     saúde de cada backend: circuit breaker, pool e requisições
+//   reload                       relê o config.yaml (como o SIGHUP)
+//   log-level [filtro]           mostra ou troca o nível de log até o próximo reload
+
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+use tracing::{info, warn};
+
+use crate::token::Redacted;
+use crate::unix_socket::UnixSocketListener;
+
+const MAX_COMMAND_LEN: u64 = 4096;
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+// --- SEÇÃO `admin` DO config.yaml ---
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AdminConfig {
+    // Caminho do socket (ex.: sygma_admin.sock)
+    pub socket: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    CacheStats,
+    CacheList,
+    CacheInvalidate(String),
+    CacheInvalidateAll,
+    Backends,
+    Reload,
+    LogLevel(Option<String>),
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["cache", "stats"] => Ok(Command::CacheStats),
+            ["cache", "list"] => Ok(Command::CacheList),
+            ["cache", "invalidate", token] => Ok(Command::CacheInvalidate(token.to_string())),
+            ["cache", "invalidate-all"] => Ok(Command::CacheInvalidateAll),
+            ["backends"] => Ok(Command::Backends),
+            ["reload"] => Ok(Command::Reload),
+            ["log-level"] => Ok(Command::LogLevel(None)),
+            ["log-level", level] => Ok(Command::LogLevel(Some(level.to_string()))),
+            [] => Err("empty command".to_string()),
+            _ => Err(format!("unknown command '{}'", line.trim())),
+        }
+    }
+}
+
+// Forma do comando para os logs: o token de `cache invalidate` nunca aparece em claro
+impl std::fmt::Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Command::CacheStats => write!(f, "cache stats"),
+            Command::CacheList => write!(f, "cache list"),
+            Command::CacheInvalidate(token) if token.starts_with("token[") => write!(f, "cache invalidate {}", token),
+            Command::CacheInvalidate(token) => write!(f, "cache invalidate {}", Redacted(token)),
+            Command::CacheInvalidateAll => write!(f, "cache invalidate-all"),
+            Command::Backends => write!(f, "backends"),
+            Command::Reload => write!(f, "reload"),
+            Command::LogLevel(None) => write!(f, "log-level"),
+            Command::LogLevel(Some(level)) => write!(f, "log-level {}", level),
+        }
+    }
+}
+
+// Executa um comando já validado; `Ok` traz o corpo da resposta
+pub type Executor = fn(Command) -> Result<String, String>;
+
+// Cria o socket com permissão 0600; se outro Proxy ainda responde nele, a inicialização falha
+pub fn bind(path: &Path) -> io::Result<UnixSocketListener> {
+    UnixSocketListener::bind(path, 0o600)
+}
+
+async fn handle(stream: UnixStream, execute: Executor) -> io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut line = String::new();
+    tokio::time::timeout(COMMAND_TIMEOUT, BufReader::new(reader.take(MAX_COMMAND_LEN)).read_line(&mut line))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "comando não recebido a tempo"))??;
+
+    let response = match Command::parse(&line) {
+        Ok(command) => {
+            info!("Comando administrativo: {}", command);
+            match execute(command) {
+                Ok(body) if body.is_empty() => "OK\n".to_string(),
+                Ok(body) => format!("OK\n{}\n", body.trim_end()),
+                Err(reason) => format!("ERR {}\n", reason),
+            }
+        }
+        Err(reason) => format!("ERR {}\n", reason),
+    };
+    writer.write_all(response.as_bytes()).await?;
+    writer.shutdown().await
+}
+
+pub async fn serve(listener: UnixSocketListener, execute: Executor) -> io::Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, execute).await {
+                warn!("Falha ao atender o socket de administração: {}", e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    // Teste 1: Comandos reconhecidos; o token de `cache invalidate` sai redigido nos logs.
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(Command::parse("cache stats\n"), Ok(Command::CacheStats));
+        assert_eq!(Command::parse("  backends "), Ok(Command::Backends));
+        assert_eq!(Command::parse("log-level warn"), Ok(Command::LogLevel(Some("warn".to_string()))));
+        assert_eq!(Command::parse("log-level"), Ok(Command::LogLevel(None)));
+        assert!(Command::parse("cache invalidate").is_err());
+        assert!(Command::parse("shutdown now").is_err());
+
+        let command = Command::parse("cache invalidate AUTH_SYGMA_VALID_conta42;sig=abcd").unwrap();
+        assert!(command.to_string().starts_with("cache invalidate token[#"));
+    }
+
+    // Teste 2: Socket com permissão 0600, uma resposta por conexão e socket antigo substituído.
+    #[tokio::test]
+    async fn test_admin_socket_roundtrip() {
+        let path = std::env::temp_dir().join(format!("sygma_admin_test_{}.sock", std::process::id()));
+        let listener = bind(&path).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(bind(&path).unwrap_err().kind(), io::ErrorKind::AddrInUse);
+
+        fn execute(command: Command) -> Result<String, String> {
+            match command {
+                Command::Reload => Err("config inválida".to_string()),
+                other => Ok(format!("executado: {}", other)),
+            }
+        }
+        tokio::spawn(serve(listener, execute));
+
+        async fn send(path: &Path, command: &str) -> String {
+            let mut stream = UnixStream::connect(path).await.unwrap();
+            stream.write_all(command.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        }
+        assert_eq!(send(&path, "backends\n").await, "OK\nexecutado: backends\n");
+        assert_eq!(send(&path, "reload\n").await, "ERR config inválida\n");
+        assert_eq!(send(&path, "desligar\n").await, "ERR unknown command 'desligar'\n");
+    }
+}
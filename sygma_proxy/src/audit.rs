@@ -0,0 +1,448 @@
+// sygma_proxy/src/audit.rs - Trilha de Auditoria Durável (JSONL) das Decisões do Proxy
+//
+// Cada decisão sobre um Settlement (rejeição 400/403/429/503, fila store-and-forward ou
+// encaminhamento ao Kernel) vira uma linha JSON: horário, origem, impressão digital do token
+// (nunca o token), SHA-256 do payload, veredito e latência do Kernel.
+//
+// A requisição só enfileira a linha (try_send, nunca bloqueia); uma thread dedicada escreve,
+// faz fsync quando a fila esvazia e rotaciona o arquivo por tamanho e por data (UTC).
+// Com a fila cheia a linha é descartada e contada em sygma_proxy_audit_records_total{result="dropped"}.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sygma_protocol::WireError;
+use tracing::error;
+
+use crate::metrics::{self, METRICS};
+use crate::token::Redacted;
+
+// --- SEÇÃO `audit` DO config.yaml ---
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AuditConfig {
+    // Arquivo corrente; os rotacionados ficam ao lado: requests-2026-10-18.jsonl, requests-2026-10-18.1.jsonl...
+    pub file: String,
+    #[serde(default = "default_max_file_bytes")]
+    pub max_file_bytes: u64,
+    // Arquivos rotacionados mantidos (os mais antigos são apagados)
+    #[serde(default = "default_max_files")]
+    pub max_files: usize,
+    // Linhas aguardando escrita; acima disso são descartadas em vez de atrasar requisições
+    #[serde(default = "default_queue_capacity")]
+    pub queue_capacity: usize,
+}
+
+fn default_max_file_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_max_files() -> usize {
+    14
+}
+
+fn default_queue_capacity() -> usize {
+    10_000
+}
+
+impl AuditConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.file.trim().is_empty() {
+            return Err("audit.file está vazio".to_string());
+        }
+        if self.max_file_bytes == 0 || self.queue_capacity == 0 {
+            return Err("audit.max_file_bytes e queue_capacity devem ser maiores que zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+// --- REGISTRO ---
+
+// Chamada ao Kernel feita durante a requisição (o último backend que recebeu o SETTLE)
+struct KernelCall {
+    backend: String,
+    latency: Duration,
+}
+
+// Acumula os dados de uma decisão ao longo do pipeline; `finish` enfileira a linha
+pub struct Entry {
+    listener: &'static str,
+    // Endereço do cliente, ou `unix:uid=...,pid=...` no socket unix
+    source: String,
+    pub cid: String,
+    token: Option<String>,
+    payload: Option<String>,
+    kernel: Option<KernelCall>,
+    // Linha de resposta do pipeline ("403 ACCESS DENIED: ..."), fonte do motivo registrado
+    response: Option<String>,
+    // Resposta original de uma chave de idempotência repetida
+    replayed: bool,
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    timestamp: String,
+    cid: &'a str,
+    listener: &'a str,
+    source: String,
+    // forwarded | queued | rejected | replayed
+    decision: &'static str,
+    status: u16,
+    reason: &'a str,
+    // Código estável do erro (sygma_protocol), quando a decisão foi um erro
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload_sha256: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backend: Option<&'a str>,
+    // OK | REJECTED | ERROR (só quando o SETTLE chegou ao Kernel)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kernel_verdict: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kernel_latency_ms: Option<f64>,
+    latency_ms: f64,
+}
+
+impl Entry {
+    pub fn new(listener: &'static str, source: String, cid: String) -> Self {
+        Entry { listener, source, cid, token: None, payload: None, kernel: None, response: None, replayed: false }
+    }
+
+    pub fn token(&mut self, token: &str) {
+        if !token.is_empty() {
+            self.token = Some(Redacted(token).to_string());
+        }
+    }
+
+    pub fn payload(&mut self, payload: &str) {
+        self.payload = Some(payload.to_string());
+    }
+
+    pub fn kernel(&mut self, backend: &str, latency: Duration) {
+        self.kernel = Some(KernelCall { backend: backend.to_string(), latency });
+    }
+
+    pub fn replayed(&mut self) {
+        self.replayed = true;
+    }
+
+    pub fn response(&mut self, line: &str) {
+        self.response = Some(line.to_string());
+    }
+
+    // `status` é o código devolvido ao cliente (no HTTP, 201 para um Settlement liquidado);
+    // o veredito do Kernel vem da linha de resposta do pipeline
+    fn to_line(&self, status: u16, elapsed: Duration, now: SystemTime) -> String {
+        let response = self.response.as_deref().unwrap_or_default();
+        // Erros: o motivo sem a marca `[err=...]`, cujo código vai em `error_code`
+        let wire = WireError::parse(response);
+        let message = match &wire {
+            Some(wire) => wire.message.as_str(),
+            None => response.get(4..).unwrap_or_default(),
+        };
+        // O payload só aparece como digest, inclusive dentro do motivo ("200 OK: Payload ... liquidado")
+        let (reason, payload_sha256) = match self.payload.as_deref().filter(|payload| !payload.is_empty()) {
+            Some(payload) => (
+                message.replace(payload, "<payload>"),
+                Some(hex::encode(Sha256::digest(payload.as_bytes()))),
+            ),
+            None => (message.to_string(), None),
+        };
+        let kernel_verdict = self.kernel.as_ref().map(|_| match metrics::status_code(response) {
+            200 => "OK",
+            422 => "REJECTED",
+            _ => "ERROR",
+        });
+        let record = Record {
+            timestamp: rfc3339(now),
+            cid: &self.cid,
+            listener: self.listener,
+            source: self.source.clone(),
+            decision: match (self.replayed, &self.kernel) {
+                (true, _) => "replayed",
+                (false, Some(_)) => "forwarded",
+                (false, None) if metrics::status_code(response) == 202 => "queued",
+                (false, None) => "rejected",
+            },
+            status,
+            reason: &reason,
+            error_code: wire.map(|wire| wire.code),
+            token: self.token.as_deref(),
+            payload_sha256: payload_sha256.as_deref(),
+            backend: self.kernel.as_ref().map(|call| call.backend.as_str()),
+            kernel_verdict,
+            kernel_latency_ms: self.kernel.as_ref().map(|call| call.latency.as_secs_f64() * 1000.0),
+            latency_ms: elapsed.as_secs_f64() * 1000.0,
+        };
+        serde_json::to_string(&record).unwrap_or_default()
+    }
+
+    // Enfileira a linha sem bloquear; sem a seção `audit`, não faz nada
+    pub fn finish(self, status: u16, elapsed: Duration) {
+        let Some(queue) = QUEUE.get() else {
+            return;
+        };
+        let line = self.to_line(status, elapsed, SystemTime::now());
+        match queue.try_send(Message::Line(line)) {
+            Ok(()) => METRICS.record_audit(true),
+            Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => METRICS.record_audit(false),
+        }
+    }
+}
+
+// --- ESCRITA E ROTAÇÃO ---
+
+enum Message {
+    Line(String),
+    // Descarrega e faz fsync; responde quando terminar (encerramento do Proxy)
+    Flush(mpsc::Sender<()>),
+}
+
+static QUEUE: OnceLock<SyncSender<Message>> = OnceLock::new();
+
+struct AuditWriter {
+    config: AuditConfig,
+    path: PathBuf,
+    file: BufWriter<File>,
+    size: u64,
+    // Data (UTC) das linhas do arquivo corrente
+    date: String,
+}
+
+impl AuditWriter {
+    fn open(config: &AuditConfig) -> io::Result<Self> {
+        let path = PathBuf::from(&config.file);
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)
+            .map_err(|e| io::Error::new(e.kind(), format!("Falha ao abrir {}: {}", path.display(), e)))?;
+        let metadata = file.metadata()?;
+        let date = utc_date(metadata.modified().unwrap_or_else(|_| SystemTime::now()));
+        Ok(AuditWriter { config: config.clone(), path, file: BufWriter::new(file), size: metadata.len(), date })
+    }
+
+    fn write(&mut self, line: &str, today: &str) -> io::Result<()> {
+        let length = line.len() as u64 + 1;
+        if self.size > 0 && (self.date != today || self.size + length > self.config.max_file_bytes) {
+            self.rotate()?;
+        }
+        self.date = today.to_string();
+        writeln!(self.file, "{}", line)?;
+        self.size += length;
+        Ok(())
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.file.get_ref().sync_data()
+    }
+
+    // requests.jsonl -> requests-<data>.jsonl (ou requests-<data>.<n>.jsonl, se já existir)
+    fn rotate(&mut self) -> io::Result<()> {
+        self.sync()?;
+        let (stem, extension) = split_name(&self.path);
+        let mut rotated = self.path.with_file_name(format!("{}-{}{}", stem, self.date, extension));
+        let mut sequence = 1;
+        while rotated.exists() {
+            rotated = self.path.with_file_name(format!("{}-{}.{}{}", stem, self.date, sequence, extension));
+            sequence += 1;
+        }
+        fs::rename(&self.path, &rotated)?;
+
+        let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.file = BufWriter::new(file);
+        self.size = 0;
+        self.prune()
+    }
+
+    // Mantém apenas os `max_files` rotacionados mais recentes
+    fn prune(&self) -> io::Result<()> {
+        let (stem, extension) = split_name(&self.path);
+        let prefix = format!("{}-", stem);
+        let directory = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let mut rotated: Vec<(SystemTime, PathBuf)> = fs::read_dir(&directory)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                name.starts_with(&prefix) && name.ends_with(&extension)
+            })
+            .map(|entry| (entry.metadata().and_then(|m| m.modified()).unwrap_or(UNIX_EPOCH), entry.path()))
+            .collect();
+        rotated.sort_by(|a, b| b.cmp(a));
+        for (_, path) in rotated.into_iter().skip(self.config.max_files) {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+// "audit/requests.jsonl" -> ("requests", ".jsonl")
+fn split_name(path: &Path) -> (String, String) {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let extension = path.extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
+    (stem, extension)
+}
+
+fn run(mut writer: AuditWriter, queue: Receiver<Message>) {
+    while let Ok(message) = queue.recv() {
+        let mut pending = Some(message);
+        // Escreve tudo o que já está na fila e só então faz o fsync
+        while let Some(message) = pending.take().or_else(|| queue.try_recv().ok()) {
+            match message {
+                Message::Line(line) => {
+                    if let Err(e) = writer.write(&line, &utc_date(SystemTime::now())) {
+                        error!("Falha ao escrever em {}: {}", writer.path.display(), e);
+                    }
+                }
+                Message::Flush(done) => {
+                    if let Err(e) = writer.sync() {
+                        error!("Falha ao descarregar {}: {}", writer.path.display(), e);
+                    }
+                    let _ = done.send(());
+                }
+            }
+        }
+        if let Err(e) = writer.sync() {
+            error!("Falha ao descarregar {}: {}", writer.path.display(), e);
+        }
+    }
+}
+
+// Abre o arquivo e inicia a thread de escrita. Chamado uma vez, na inicialização.
+pub fn init(config: Option<&AuditConfig>) -> io::Result<()> {
+    let Some(config) = config else {
+        return Ok(());
+    };
+    let writer = AuditWriter::open(config)?;
+    let (sender, receiver) = mpsc::sync_channel(config.queue_capacity);
+    std::thread::Builder::new()
+        .name("sygma-audit".to_string())
+        .spawn(move || run(writer, receiver))?;
+    let _ = QUEUE.set(sender);
+    Ok(())
+}
+
+// Encerramento: espera as linhas enfileiradas chegarem ao disco (até `timeout`)
+pub async fn flush(timeout: Duration) -> bool {
+    let Some(queue) = QUEUE.get().cloned() else {
+        return true;
+    };
+    tokio::task::spawn_blocking(move || {
+        let (done, flushed) = mpsc::channel();
+        queue.send(Message::Flush(done)).is_ok() && flushed.recv_timeout(timeout).is_ok()
+    }).await.unwrap_or(false)
+}
+
+// --- DATAS (UTC) ---
+
+// Dias desde 1970-01-01 -> (ano, mês, dia), algoritmo de Howard Hinnant
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn utc_date(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn rfc3339(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs_of_day = since_epoch.as_secs() % 86_400;
+    format!(
+        "{}T{:02}:{:02}:{:02}.{:03}Z",
+        utc_date(time), secs_of_day / 3600, secs_of_day % 3600 / 60, secs_of_day % 60, since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sygma_audit_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // Teste 1: A linha traz a decisão, a impressão digital do token e o digest do payload, nunca os originais.
+    #[test]
+    fn test_record_line() {
+        let mut entry = Entry::new("tcp", "10.0.0.7:5000".to_string(), "cid42".to_string());
+        entry.token("AUTH_SYGMA_VALID_conta42;sig=segredo");
+        entry.payload("ZKP_HASH_S:1_R:2_A:3");
+        entry.kernel("127.0.0.1:8080", Duration::from_millis(12));
+        entry.response("200 OK: Payload ZKP_HASH_S:1_R:2_A:3 liquidado pelo Kernel T1 (Prova ZKP_COMMITMENT_7).");
+        let line = entry.to_line(201, Duration::from_millis(15), UNIX_EPOCH + Duration::from_millis(1_760_000_000_123));
+
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["timestamp"], "2025-10-09T08:53:20.123Z");
+        assert_eq!(json["decision"], "forwarded");
+        assert_eq!(json["status"], 201);
+        assert_eq!(json["kernel_verdict"], "OK");
+        assert_eq!(json["reason"], "OK: Payload <payload> liquidado pelo Kernel T1 (Prova ZKP_COMMITMENT_7).");
+        assert_eq!(json["source"], "10.0.0.7:5000");
+        assert!(json.get("error_code").is_none());
+        assert!(json["token"].as_str().unwrap().starts_with("token[#"));
+        assert_eq!(json["payload_sha256"].as_str().unwrap().len(), 64);
+        assert!(!line.contains("segredo") && !line.contains("ZKP_HASH"));
+
+        let mut rejected = Entry::new("http", "10.0.0.7:5000".to_string(), "cid43".to_string());
+        rejected.response(&sygma_protocol::SygmaError::ZeroTrustViolation.response());
+        let json: serde_json::Value = serde_json::from_str(&rejected.to_line(403, Duration::ZERO, SystemTime::now())).unwrap();
+        assert_eq!(json["decision"], "rejected");
+        assert_eq!(json["reason"], "ACCESS DENIED: Zero Trust Violation");
+        assert_eq!(json["error_code"], sygma_protocol::code::ZERO_TRUST_VIOLATION);
+        assert!(json.get("kernel_verdict").is_none());
+    }
+
+    // Teste 2: Rotação por tamanho e por data, mantendo só os `max_files` mais recentes.
+    #[test]
+    fn test_rotation() {
+        let dir = temp_dir("rotation");
+        let config = AuditConfig {
+            file: dir.join("requests.jsonl").to_string_lossy().to_string(),
+            max_file_bytes: 20,
+            max_files: 2,
+            queue_capacity: 10,
+        };
+        let mut writer = AuditWriter::open(&config).unwrap();
+
+        writer.write("{\"n\":1,\"xxxxxx\":1}", "2026-10-17").unwrap();
+        writer.write("{\"n\":2}", "2026-10-18").unwrap();
+        assert!(dir.join("requests-2026-10-17.jsonl").exists(), "Mudança de data rotaciona.");
+        writer.write("{\"n\":3,\"xxxxxx\":1}", "2026-10-18").unwrap();
+        writer.write("{\"n\":4,\"xxxxxx\":1}", "2026-10-18").unwrap();
+        writer.sync().unwrap();
+
+        assert!(!dir.join("requests-2026-10-17.jsonl").exists(), "Só max_files rotacionados são mantidos.");
+        assert!(dir.join("requests-2026-10-18.jsonl").exists(), "Tamanho acima do limite rotaciona.");
+        assert!(dir.join("requests-2026-10-18.1.jsonl").exists());
+        assert_eq!(fs::read_to_string(dir.join("requests.jsonl")).unwrap(), "{\"n\":4,\"xxxxxx\":1}\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,260 @@
+// sygma_proxy/src/balancer.rs - Balanceamento entre Múltiplos Backends do Kernel (T1)
+//
+// Cada backend tem seu próprio pool de conexões e seu próprio circuit breaker: um backend
+// com o circuito aberto sai da rotação automaticamente até se recuperar.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::health::{Admission, CircuitBreaker};
+use crate::kernel_pool::KernelPool;
+
+// Pontos virtuais por unidade de peso no anel de hash consistente
+const VIRTUAL_NODES_PER_WEIGHT: u32 = 64;
+
+// --- ENTRADAS `kernel_backends` DO config.yaml ---
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct BackendConfig {
+    pub address: String,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    #[default]
+    RoundRobin,
+    LeastConnections,
+    // Mesma conta de origem -> mesmo backend (enquanto ele estiver saudável)
+    ConsistentHash,
+}
+
+pub struct Backend {
+    pub address: String,
+    pub weight: u32,
+    pub pool: KernelPool,
+    pub breaker: CircuitBreaker,
+    active: AtomicUsize,
+}
+
+// Marca uma requisição em andamento no backend (base do least-connections)
+pub struct ActiveRequest<'a>(&'a Backend);
+
+impl Drop for ActiveRequest<'_> {
+    fn drop(&mut self) {
+        self.0.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Backend {
+    fn new(config: &BackendConfig, global: &Config) -> Self {
+        Backend {
+            address: config.address.clone(),
+            weight: config.weight.max(1),
+            pool: KernelPool::new(&config.address, global.kernel_pool.clone()),
+            breaker: CircuitBreaker::new(global.health.clone()),
+            active: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn begin(&self) -> ActiveRequest<'_> {
+        self.active.fetch_add(1, Ordering::SeqCst);
+        ActiveRequest(self)
+    }
+
+    pub fn active_requests(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    fn load(&self) -> usize {
+        // Normalizado pelo peso: um backend de peso 2 aguenta o dobro
+        self.active.load(Ordering::SeqCst) * 1_000 / self.weight as usize
+    }
+}
+
+pub struct Selected {
+    pub index: usize,
+    pub backend: Arc<Backend>,
+    pub admission: Admission,
+}
+
+pub struct Balancer {
+    strategy: Strategy,
+    backends: Vec<Arc<Backend>>,
+    // Round-robin ponderado: cada índice aparece `weight` vezes
+    schedule: Vec<usize>,
+    // Anel de hash consistente ordenado: (hash, índice do backend)
+    ring: Vec<(u64, usize)>,
+    next: AtomicUsize,
+}
+
+fn hash_of(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl Balancer {
+    // Reaproveita backends (pool e breaker) de `previous` cujo endereço continua configurado
+    pub fn new(config: &Config, previous: Option<&Balancer>) -> Self {
+        let backends: Vec<Arc<Backend>> = config.backends().iter()
+            .map(|backend_config| {
+                previous
+                    .and_then(|prev| prev.backends.iter().find(|b| b.address == backend_config.address && b.weight == backend_config.weight.max(1)))
+                    .cloned()
+                    .unwrap_or_else(|| Arc::new(Backend::new(backend_config, config)))
+            })
+            .collect();
+
+        let schedule = backends.iter().enumerate()
+            .flat_map(|(index, backend)| std::iter::repeat_n(index, backend.weight as usize))
+            .collect();
+
+        let mut ring: Vec<(u64, usize)> = backends.iter().enumerate()
+            .flat_map(|(index, backend)| {
+                (0..backend.weight * VIRTUAL_NODES_PER_WEIGHT).map(move |node| (hash_of((&backend.address, node)), index))
+            })
+            .collect();
+        ring.sort_unstable();
+
+        Balancer { strategy: config.load_balancing, backends, schedule, ring, next: AtomicUsize::new(0) }
+    }
+
+    // A configuração ativa ainda descreve este balanceador?
+    pub fn matches(&self, config: &Config) -> bool {
+        let configured = config.backends();
+        self.strategy == config.load_balancing
+            && self.backends.len() == configured.len()
+            && self.backends.iter().zip(&configured).all(|(b, c)| b.address == c.address && b.weight == c.weight.max(1))
+    }
+
+    pub fn backends(&self) -> &[Arc<Backend>] {
+        &self.backends
+    }
+
+    // Próximo candidato segundo a estratégia, ignorando os excluídos
+    fn candidate(&self, key: Option<&str>, excluded: &[bool]) -> Option<usize> {
+        let strategy = match (self.strategy, key) {
+            (Strategy::ConsistentHash, None) => Strategy::RoundRobin,
+            (strategy, _) => strategy,
+        };
+
+        match strategy {
+            Strategy::RoundRobin => {
+                let start = self.next.fetch_add(1, Ordering::Relaxed);
+                (0..self.schedule.len())
+                    .map(|offset| self.schedule[(start + offset) % self.schedule.len()])
+                    .find(|&index| !excluded[index])
+            }
+            Strategy::LeastConnections => (0..self.backends.len())
+                .filter(|&index| !excluded[index])
+                .min_by_key(|&index| self.backends[index].load()),
+            Strategy::ConsistentHash => {
+                let point = hash_of(key.unwrap_or_default());
+                let start = self.ring.partition_point(|&(hash, _)| hash < point);
+                (0..self.ring.len())
+                    .map(|offset| self.ring[(start + offset) % self.ring.len()].1)
+                    .find(|&index| !excluded[index])
+            }
+        }
+    }
+
+    // Escolhe um backend admitido pelo seu circuit breaker. `excluded` acumula os backends
+    // já tentados nesta requisição (failover). `Err` traz o menor Retry-After entre os
+    // circuitos abertos, ou `None` se todos falharam nesta requisição.
+    pub fn select(&self, key: Option<&str>, excluded: &mut [bool]) -> Result<Selected, Option<Duration>> {
+        let mut retry_after: Option<Duration> = None;
+
+        while let Some(index) = self.candidate(key, excluded) {
+            let backend = &self.backends[index];
+            match backend.breaker.admit() {
+                Ok(admission) => return Ok(Selected { index, backend: backend.clone(), admission }),
+                Err(wait) => {
+                    excluded[index] = true;
+                    retry_after = Some(retry_after.map_or(wait, |current| current.min(wait)));
+                }
+            }
+        }
+        Err(retry_after)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::HealthConfig;
+
+    fn config(strategy: &str, backends: &[(&str, u32)]) -> Config {
+        let mut yaml = format!("proxy_address: \"127.0.0.1:7979\"\nload_balancing: {}\nkernel_backends:\n", strategy);
+        for (address, weight) in backends {
+            yaml.push_str(&format!("  - address: \"{}\"\n    weight: {}\n", address, weight));
+        }
+        serde_yaml::from_str(&yaml).unwrap()
+    }
+
+    fn pick(balancer: &Balancer, key: Option<&str>) -> String {
+        let mut excluded = vec![false; balancer.backends().len()];
+        balancer.select(key, &mut excluded).ok().unwrap().backend.address.clone()
+    }
+
+    // Teste 1: Round-robin respeita os pesos.
+    #[test]
+    fn test_weighted_round_robin() {
+        let balancer = Balancer::new(&config("round_robin", &[("a:1", 2), ("b:1", 1)]), None);
+        let picks: Vec<String> = (0..6).map(|_| pick(&balancer, None)).collect();
+        assert_eq!(picks.iter().filter(|p| *p == "a:1").count(), 4);
+        assert_eq!(picks.iter().filter(|p| *p == "b:1").count(), 2);
+    }
+
+    // Teste 2: Hash consistente mantém a mesma conta no mesmo backend e desvia de backends fora do ar.
+    #[test]
+    fn test_consistent_hash_and_unhealthy_backend() {
+        let balancer = Balancer::new(&config("consistent_hash", &[("a:1", 1), ("b:1", 1), ("c:1", 1)]), None);
+        let first = pick(&balancer, Some("conta42"));
+        assert!((0..10).all(|_| pick(&balancer, Some("conta42")) == first));
+
+        // Derruba o backend escolhido: a conta vai para outro, sem erro
+        let down = balancer.backends().iter().find(|b| b.address == first).unwrap();
+        for _ in 0..HealthConfig::default().failure_threshold {
+            down.breaker.record_probe(false);
+        }
+        let failover = pick(&balancer, Some("conta42"));
+        assert_ne!(failover, first, "Backend com circuito aberto sai da rotação.");
+    }
+
+    // Teste 3: Least-connections evita o backend ocupado.
+    #[test]
+    fn test_least_connections() {
+        let balancer = Balancer::new(&config("least_connections", &[("a:1", 1), ("b:1", 1)]), None);
+        let busy = &balancer.backends()[0];
+        let _request = busy.begin();
+        assert_eq!(pick(&balancer, None), "b:1");
+    }
+
+    // Teste 4: Sem backends admitidos, o erro traz o Retry-After; backends são reaproveitados no reload.
+    #[test]
+    fn test_all_open_and_reuse() {
+        let config = config("round_robin", &[("a:1", 1)]);
+        let balancer = Balancer::new(&config, None);
+        for _ in 0..HealthConfig::default().failure_threshold {
+            balancer.backends()[0].breaker.record_probe(false);
+        }
+        let mut excluded = vec![false];
+        assert!(matches!(balancer.select(None, &mut excluded), Err(Some(_))));
+
+        let reloaded = Balancer::new(&config, Some(&balancer));
+        assert!(Arc::ptr_eq(&reloaded.backends()[0], &balancer.backends()[0]));
+        assert!(reloaded.matches(&config));
+    }
+}
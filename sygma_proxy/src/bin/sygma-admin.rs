@@ -0,0 +1,79 @@
+// sygma_proxy/src/bin/sygma-admin.rs - Cliente do Socket de Administração do Proxy
+//
+// Uso: sygma-admin [--socket <caminho>] <comando> [argumentos]
+// Ex.: sygma-admin cache stats | sygma-admin backends | sygma-admin log-level debug
+// O socket padrão vem de SYGMA_ADMIN_SOCKET (ou sygma_admin.sock, como em admin.socket do config.yaml).
+
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::process::ExitCode;
+use std::time::Duration;
+
+const SOCKET_ENV: &str = "SYGMA_ADMIN_SOCKET";
+const DEFAULT_SOCKET: &str = "sygma_admin.sock";
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+const USAGE: &str = "Uso: sygma-admin [--socket <caminho>] <comando>
+
+Comandos:
+  cache stats                  entradas, capacidade, hits e misses do TRUST_CACHE
+  cache list                   entradas do TRUST_CACHE (tokens redigidos)
+  cache invalidate <token>     token em claro ou a forma redigida de `cache list`
+  cache invalidate-all
+  backends                     circuit breaker, pool e requisições de cada backend
+  reload                       relê o config.yaml
+  log-level [filtro]           mostra ou troca o nível de log";
+
+fn send(socket: &str, command: &str) -> io::Result<String> {
+    let mut stream = UnixStream::connect(socket)
+        .map_err(|e| io::Error::new(e.kind(), format!("Falha ao conectar a {}: {}. O Proxy está rodando com a seção admin?", socket, e)))?;
+    stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+    stream.write_all(format!("{}\n", command).as_bytes())?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    Ok(response)
+}
+
+fn main() -> ExitCode {
+    let mut socket = std::env::var(SOCKET_ENV).unwrap_or_else(|_| DEFAULT_SOCKET.to_string());
+    let mut words = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--socket" => match args.next() {
+                Some(path) => socket = path,
+                None => {
+                    eprintln!("--socket exige um caminho");
+                    return ExitCode::from(2);
+                }
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ => words.push(arg),
+        }
+    }
+    if words.is_empty() {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    }
+
+    match send(&socket, &words.join(" ")) {
+        // Resposta: `OK` seguido do corpo, ou `ERR <motivo>`
+        Ok(response) => match response.strip_prefix("OK\n").or_else(|| response.strip_prefix("OK")) {
+            Some(body) => {
+                print!("{}", body);
+                ExitCode::SUCCESS
+            }
+            None => {
+                eprint!("{}", response);
+                ExitCode::FAILURE
+            }
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,190 @@
+// sygma_proxy/src/cache.rs - Política Configurável do TRUST_CACHE (TinyLFU)
+
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use moka::policy::EvictionPolicy;
+use moka::sync::Cache;
+use moka::Expiry;
+use serde::Deserialize;
+
+use crate::token::TokenClaims;
+
+// --- RELÓGIO: permite controlar o tempo nos testes de expiração ---
+pub trait Clock: Send + Sync {
+    fn now_unix(&self) -> u64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_unix(&self) -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Eviction {
+    TinyLfu,
+    Lru,
+}
+
+// --- SEÇÃO `cache` DO config.yaml ---
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub max_capacity: u64,
+    pub time_to_live_secs: u64,
+    // Remove entradas sem acesso há este tempo (opcional)
+    pub time_to_idle_secs: Option<u64>,
+    pub eviction: Eviction,
+    // Quando ativo, cada entrada expira no `exp` do próprio token (se vier antes do TTL)
+    pub use_token_exp: bool,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            max_capacity: 10_000,
+            time_to_live_secs: 300,
+            time_to_idle_secs: None,
+            eviction: Eviction::TinyLfu,
+            use_token_exp: true,
+        }
+    }
+}
+
+// Expiração por entrada derivada do claim `exp` do token
+pub struct TokenExpiry {
+    clock: Arc<dyn Clock>,
+}
+
+impl TokenExpiry {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        TokenExpiry { clock }
+    }
+}
+
+impl Expiry<String, TokenClaims> for TokenExpiry {
+    fn expire_after_create(&self, _token: &String, claims: &TokenClaims, _created_at: Instant) -> Option<Duration> {
+        let exp = claims.exp?;
+        Some(Duration::from_secs(exp.saturating_sub(self.clock.now_unix())))
+    }
+}
+
+// Entrada do TRUST_CACHE ainda válida no relógio. O TTL do moka corre no relógio monotônico e,
+// sem `use_token_exp`, nem olha o `exp`: uma entrada expirada é removida aqui.
+pub fn cached_claims(cache: &Cache<String, TokenClaims>, key: &str, now_unix: u64) -> Option<TokenClaims> {
+    let claims = cache.get(key)?;
+    if claims.is_expired(now_unix) {
+        cache.invalidate(key);
+        return None;
+    }
+    Some(claims)
+}
+
+pub fn build_trust_cache(config: &CacheConfig, clock: Arc<dyn Clock>) -> Cache<String, TokenClaims> {
+    let eviction_policy = match config.eviction {
+        Eviction::TinyLfu => EvictionPolicy::tiny_lfu(),
+        Eviction::Lru => EvictionPolicy::lru(),
+    };
+
+    let mut builder = Cache::builder()
+        .max_capacity(config.max_capacity)
+        .time_to_live(Duration::from_secs(config.time_to_live_secs))
+        .eviction_policy(eviction_policy);
+
+    if let Some(idle_secs) = config.time_to_idle_secs {
+        builder = builder.time_to_idle(Duration::from_secs(idle_secs));
+    }
+    if config.use_token_exp {
+        return builder.expire_after(TokenExpiry::new(clock)).build();
+    }
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    // Relógio controlado pelo teste
+    struct MockClock(AtomicU64);
+
+    impl MockClock {
+        fn advance(&self, secs: u64) {
+            self.0.fetch_add(secs, Ordering::SeqCst);
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now_unix(&self) -> u64 {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    fn claims(exp: Option<u64>) -> TokenClaims {
+        TokenClaims { subject: "conta42".to_string(), exp, tier: None, scopes: Vec::new(), nonce: None, ts: None }
+    }
+
+    // Teste 1: A expiração por entrada acompanha o relógio até o `exp` do token.
+    #[test]
+    fn test_token_expiry_follows_clock() {
+        let clock = Arc::new(MockClock(AtomicU64::new(1_000)));
+        let expiry = TokenExpiry::new(clock.clone());
+        let key = "AUTH_SYGMA_VALID_conta42;exp=1060".to_string();
+
+        let ttl = expiry.expire_after_create(&key, &claims(Some(1_060)), Instant::now());
+        assert_eq!(ttl, Some(Duration::from_secs(60)));
+
+        clock.advance(45);
+        let ttl = expiry.expire_after_create(&key, &claims(Some(1_060)), Instant::now());
+        assert_eq!(ttl, Some(Duration::from_secs(15)));
+
+        clock.advance(100);
+        let ttl = expiry.expire_after_create(&key, &claims(Some(1_060)), Instant::now());
+        assert_eq!(ttl, Some(Duration::ZERO), "Token já expirado não deve permanecer no cache.");
+        assert!(claims(Some(1_060)).is_expired(clock.now_unix()));
+    }
+
+    // Teste 2: Sem `exp`, valem apenas o TTL/TTI configurados.
+    #[test]
+    fn test_token_without_exp_uses_cache_ttl() {
+        let expiry = TokenExpiry::new(Arc::new(MockClock(AtomicU64::new(1_000))));
+        let ttl = expiry.expire_after_create(&"AUTH_SYGMA_VALID_x".to_string(), &claims(None), Instant::now());
+        assert_eq!(ttl, None);
+    }
+
+    // Teste 3: Um token cujo `exp` já passou no relógio é descartado pelo cache.
+    #[test]
+    fn test_expired_token_not_served_from_cache() {
+        let clock = Arc::new(MockClock(AtomicU64::new(5_000)));
+        let cache = build_trust_cache(&CacheConfig::default(), clock.clone());
+
+        cache.insert("valido".to_string(), claims(Some(5_300)));
+        cache.insert("expirado".to_string(), claims(Some(4_999)));
+
+        assert!(cache.get("valido").is_some());
+        assert!(cache.get("expirado").is_none(), "Entrada com exp no passado deve expirar imediatamente.");
+    }
+
+    // Teste 4: Com o relógio além do `exp`, a entrada deixa de ser servida e sai do cache, mesmo
+    // sem `use_token_exp` (o TTL do moka ainda não venceu).
+    #[test]
+    fn test_cached_claims_recheck_expiry() {
+        for use_token_exp in [true, false] {
+            let clock = Arc::new(MockClock(AtomicU64::new(5_000)));
+            let config = CacheConfig { use_token_exp, ..CacheConfig::default() };
+            let cache = build_trust_cache(&config, clock.clone());
+            cache.insert("token".to_string(), claims(Some(5_060)));
+            cache.insert("sem_exp".to_string(), claims(None));
+
+            assert!(cached_claims(&cache, "token", clock.now_unix()).is_some());
+            clock.advance(60);
+            assert!(cached_claims(&cache, "token", clock.now_unix()).is_none(), "use_token_exp: {}", use_token_exp);
+            assert!(cache.get("token").is_none(), "A entrada expirada é invalidada.");
+            assert!(cached_claims(&cache, "sem_exp", clock.now_unix()).is_some());
+        }
+    }
+}
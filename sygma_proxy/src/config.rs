@@ -0,0 +1,706 @@
+// sygma_proxy/src/config.rs - Configuração YAML com Hot Reload (arquivo vigiado + SIGHUP)
+//
+// Ordem de carga: YAML -> overrides SYGMA_PROXY_* -> interpolação ${VAR} / file: -> validação.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{error, info, warn};
+
+use crate::admin::AdminConfig;
+use crate::audit::AuditConfig;
+use crate::balancer::{BackendConfig, Strategy};
+use crate::cache::CacheConfig;
+use crate::connections::ConnectionConfig;
+use crate::health::HealthConfig;
+use crate::http_api::HttpConfig;
+use crate::idempotency::IdempotencyConfig;
+use crate::kernel_pool::PoolConfig;
+use crate::lockout::LockoutConfig;
+use crate::logging::{self, LoggingConfig};
+use crate::metrics::MetricsConfig;
+use crate::policy::PolicyConfig;
+use crate::ratelimit::RateLimitConfig;
+use crate::replay::ReplayConfig;
+use crate::shutdown::ShutdownConfig;
+use crate::store_forward::StoreForwardConfig;
+use crate::tls::{self, TlsConfig};
+use crate::unix_socket::{self, UnixSocketConfig};
+use crate::watch::file_signature;
+use crate::TRUST_CACHE;
+
+pub const DEFAULT_CONFIG_PATH: &str = "config.yaml";
+
+// Prefixo das variáveis de ambiente que sobrescrevem campos do YAML.
+// Seções aninhadas usam `__`: SYGMA_PROXY_CACHE__MAX_CAPACITY=5000; itens de lista, o índice:
+// SYGMA_PROXY_KERNEL_BACKENDS__0__ADDRESS=10.0.0.5:8080
+const ENV_PREFIX: &str = "SYGMA_PROXY_";
+
+// --- ESTRUTURA DE DADOS DA CONFIGURAÇÃO YAML ---
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct Config {
+    // host:porta ou unix:/caminho (ver seção `unix_socket`)
+    pub proxy_address: String,
+    // Backend único (formato antigo); ignorado quando `kernel_backends` está presente
+    #[serde(default)]
+    pub kernel_address: Option<String>,
+    // Backends do Kernel com pesos, e a estratégia de balanceamento entre eles
+    #[serde(default)]
+    pub kernel_backends: Vec<BackendConfig>,
+    #[serde(default)]
+    pub load_balancing: Strategy,
+    // Arquivo com a lista de tokens revogados (opcional, vigiado em tempo de execução)
+    #[serde(default)]
+    pub revocation_file: Option<String>,
+    // Política do TRUST_CACHE (capacidade, TTL, TTI, expiração pelo token)
+    #[serde(default)]
+    pub cache: CacheConfig,
+    // Cache negativo e bloqueio de IPs com falhas repetidas
+    #[serde(default)]
+    pub lockout: LockoutConfig,
+    // Limites por IP e por token (faixas por classe de token)
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    // Regras de negócio avaliadas antes do encaminhamento ao Kernel (hot reload)
+    #[serde(default)]
+    pub policy: PolicyConfig,
+    // Janela das chaves de idempotência
+    #[serde(default)]
+    pub idempotency: IdempotencyConfig,
+    // Nonce e timestamp dos tokens (proteção contra replay)
+    #[serde(default)]
+    pub replay: ReplayConfig,
+    // Permissão do socket e uids aceitos quando proxy_address é unix:/caminho
+    #[serde(default)]
+    pub unix_socket: UnixSocketConfig,
+    // Timeouts e limites das conexões de clientes
+    #[serde(default)]
+    pub connections: ConnectionConfig,
+    // Pool de conexões persistentes com o Kernel
+    #[serde(default)]
+    pub kernel_pool: PoolConfig,
+    // Monitor de saúde do Kernel e circuit breaker
+    #[serde(default)]
+    pub health: HealthConfig,
+    // API HTTP/JSON (ausente = só o protocolo TCP)
+    #[serde(default)]
+    pub http: Option<HttpConfig>,
+    // Endpoint /metrics do Prometheus (ausente = desativado)
+    #[serde(default)]
+    pub metrics: Option<MetricsConfig>,
+    // Socket de administração local (ausente = desativado)
+    #[serde(default)]
+    pub admin: Option<AdminConfig>,
+    // Nível e formato dos logs (texto ou JSON)
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    // Prazo de drenagem no encerramento (SIGTERM / SIGINT)
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+    // Trilha de auditoria JSONL das decisões (ausente = desativada)
+    #[serde(default)]
+    pub audit: Option<AuditConfig>,
+    // Fila durável de Settlements com o Kernel fora do ar (ausente = 503 imediato)
+    #[serde(default)]
+    pub store_and_forward: Option<StoreForwardConfig>,
+    // TLS no listener (ausente = TCP puro)
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    // Autenticação dos tokens (chave HMAC)
+    #[serde(default)]
+    pub auth: AuthConfig,
+}
+
+// --- SEÇÃO `auth` DO config.yaml ---
+#[derive(Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    // Quando definida, todo token precisa de `;sig=<hex>` (HMAC-SHA256). Use ${VAR} ou file:
+    pub hmac_key: Option<String>,
+}
+
+// A chave nunca aparece em logs
+impl fmt::Debug for AuthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthConfig")
+            .field("hmac_key", &self.hmac_key.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+impl Config {
+    // Lista efetiva de backends (`kernel_address` vira um backend de peso 1)
+    pub fn backends(&self) -> Vec<BackendConfig> {
+        if !self.kernel_backends.is_empty() {
+            return self.kernel_backends.clone();
+        }
+        self.kernel_address.iter()
+            .map(|address| BackendConfig { address: address.clone(), weight: 1 })
+            .collect()
+    }
+
+    // Validação semântica, feita ANTES de qualquer troca de configuração
+    pub fn validate(&self) -> Result<(), String> {
+        match unix_socket::socket_path(&self.proxy_address) {
+            Some(path) if path.as_os_str().is_empty() => return Err("proxy_address: caminho vazio em 'unix:'".to_string()),
+            Some(_) => {}
+            None => {
+                self.proxy_address.parse::<SocketAddr>()
+                    .map_err(|e| format!("proxy_address inválido '{}': {}", self.proxy_address, e))?;
+            }
+        }
+        self.unix_socket.validate()?;
+        let backends = self.backends();
+        if backends.is_empty() {
+            return Err("defina kernel_backends (ou kernel_address)".to_string());
+        }
+        for backend in &backends {
+            let valid = match unix_socket::socket_path(&backend.address) {
+                Some(path) => !path.as_os_str().is_empty(),
+                None => backend.address.rsplit_once(':').and_then(|(_, port)| port.parse::<u16>().ok()).is_some(),
+            };
+            if !valid {
+                return Err(format!("endereço de Kernel inválido '{}': esperado host:porta ou unix:/caminho", backend.address));
+            }
+            if backend.weight == 0 {
+                return Err(format!("peso do backend '{}' deve ser maior que zero", backend.address));
+            }
+        }
+        if self.cache.max_capacity == 0 {
+            return Err("cache.max_capacity deve ser maior que zero".to_string());
+        }
+        if self.kernel_pool.max_size == 0 || self.kernel_pool.max_in_flight_per_connection == 0 {
+            return Err("kernel_pool.max_size e max_in_flight_per_connection devem ser maiores que zero".to_string());
+        }
+        if self.health.probe_interval_ms == 0 || self.health.failure_threshold == 0 {
+            return Err("health.probe_interval_ms e failure_threshold devem ser maiores que zero".to_string());
+        }
+        if self.lockout.max_failures == 0 {
+            return Err("lockout.max_failures deve ser maior que zero".to_string());
+        }
+        if self.idempotency.window_secs == 0 || self.idempotency.max_keys == 0 {
+            return Err("idempotency.window_secs e max_keys devem ser maiores que zero".to_string());
+        }
+        if self.replay.max_skew_secs == 0 || self.replay.max_tracked_nonces == 0 {
+            return Err("replay.max_skew_secs e max_tracked_nonces devem ser maiores que zero".to_string());
+        }
+        self.rate_limit.validate()?;
+        self.policy.validate()?;
+        self.connections.validate()?;
+        if let Some(http) = &self.http {
+            http.address.parse::<SocketAddr>()
+                .map_err(|e| format!("http.address inválido '{}': {}", http.address, e))?;
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.address.parse::<SocketAddr>()
+                .map_err(|e| format!("metrics.address inválido '{}': {}", metrics.address, e))?;
+        }
+        if self.admin.as_ref().is_some_and(|admin| admin.socket.is_empty()) {
+            return Err("admin.socket está vazio".to_string());
+        }
+        logging::parse_level(&self.logging.level)?;
+        if let Some(audit) = &self.audit {
+            audit.validate()?;
+        }
+        if let Some(store_and_forward) = &self.store_and_forward {
+            store_and_forward.validate()?;
+        }
+        if let Some(tls) = &self.tls {
+            tls::build_listener(tls).map_err(|e| format!("tls: {}", e))?;
+        }
+        if self.auth.hmac_key.as_deref() == Some("") {
+            return Err("auth.hmac_key está vazia".to_string());
+        }
+        if self.replay.require_nonce && self.auth.hmac_key.is_none() {
+            return Err("replay.require_nonce exige auth.hmac_key: sem assinatura, nonce e ts não protegem contra replay".to_string());
+        }
+        if !self.rate_limit.tiers.is_empty() && self.auth.hmac_key.is_none() {
+            return Err("rate_limit.tiers exige auth.hmac_key: sem assinatura, qualquer token se declara `tier`".to_string());
+        }
+        if !self.policy.scopes.is_empty() && self.auth.hmac_key.is_none() {
+            return Err("policy.scopes exige auth.hmac_key: sem assinatura, qualquer token troca o próprio `scope`".to_string());
+        }
+        Ok(())
+    }
+
+    // Seções lidas apenas na inicialização: mudanças exigem reiniciar o Proxy
+    fn restart_required_changes(&self, new: &Config) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.proxy_address != new.proxy_address {
+            changed.push("proxy_address");
+        }
+        if self.unix_socket.mode != new.unix_socket.mode {
+            changed.push("unix_socket.mode");
+        }
+        if self.cache != new.cache {
+            changed.push("cache");
+        }
+        if self.lockout != new.lockout {
+            changed.push("lockout");
+        }
+        if self.idempotency != new.idempotency {
+            changed.push("idempotency");
+        }
+        if self.replay != new.replay {
+            changed.push("replay");
+        }
+        if self.kernel_pool != new.kernel_pool {
+            changed.push("kernel_pool");
+        }
+        if self.health != new.health {
+            changed.push("health");
+        }
+        if (self.rate_limit.max_tracked_keys, self.rate_limit.idle_ttl_secs)
+            != (new.rate_limit.max_tracked_keys, new.rate_limit.idle_ttl_secs)
+        {
+            changed.push("rate_limit.max_tracked_keys/idle_ttl_secs");
+        }
+        if self.http != new.http {
+            changed.push("http");
+        }
+        if self.metrics != new.metrics {
+            changed.push("metrics");
+        }
+        if self.admin != new.admin {
+            changed.push("admin");
+        }
+        if self.logging.format != new.logging.format {
+            changed.push("logging.format");
+        }
+        if self.audit != new.audit {
+            changed.push("audit");
+        }
+        if self.store_and_forward != new.store_and_forward {
+            changed.push("store_and_forward");
+        }
+        if self.tls.is_some() != new.tls.is_some() {
+            changed.push("tls (ativar/desativar)");
+        }
+        changed
+    }
+}
+
+// Configuração ativa. Cada conexão captura um snapshot (Arc) no início,
+// então uma recarga só afeta conexões NOVAS; as requisições em andamento seguem intactas.
+static APP_CONFIG: OnceLock<RwLock<Arc<Config>>> = OnceLock::new();
+static CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn config_path() -> &'static Path {
+    CONFIG_PATH.get_or_init(|| PathBuf::from(DEFAULT_CONFIG_PATH))
+}
+
+// Carrega a configuração de `path` na inicialização. Os erros voltam para o chamador (sem panic).
+pub fn init(path: PathBuf) -> Result<Arc<Config>, io::Error> {
+    let config = Arc::new(load_config(&path)?);
+    let _ = CONFIG_PATH.set(path);
+    let _ = APP_CONFIG.set(RwLock::new(config.clone()));
+    Ok(config)
+}
+
+fn active() -> &'static RwLock<Arc<Config>> {
+    APP_CONFIG.get_or_init(|| RwLock::new(Arc::new(initial_config())))
+}
+
+#[cfg(not(test))]
+fn initial_config() -> Config {
+    load_config(config_path()).expect("Falha ao carregar config.yaml. O arquivo existe?")
+}
+
+// Nos testes: o config.yaml do repositório com assinatura HMAC (token::TEST_HMAC_KEY) e nonce
+// obrigatório, independente do ambiente de quem roda os testes
+#[cfg(test)]
+fn initial_config() -> Config {
+    let env = HashMap::from([
+        ("SYGMA_PROXY_AUTH__HMAC_KEY".to_string(), crate::token::TEST_HMAC_KEY.to_string()),
+        ("SYGMA_PROXY_REPLAY__REQUIRE_NONCE".to_string(), "true".to_string()),
+    ]);
+    let contents = std::fs::read_to_string(config_path()).expect("config.yaml do repositório");
+    parse_config(&contents, &env).expect("config.yaml do repositório inválido")
+}
+
+pub fn current() -> Arc<Config> {
+    active().read().unwrap().clone()
+}
+
+// --- FUNÇÃO DE LEITURA DA CONFIGURAÇÃO ---
+pub fn load_config(path: &Path) -> Result<Config, io::Error> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("Falha ao ler {}: {}", path.display(), e)))?;
+    parse_config(&contents, &std::env::vars().collect())
+}
+
+fn parse_config(contents: &str, env: &HashMap<String, String>) -> Result<Config, io::Error> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+    let mut value: Value = serde_yaml::from_str(contents)
+        .map_err(|e| invalid(format!("Erro de parse YAML: {}", e)))?;
+    let overrides = apply_env_overrides(&mut value, env).map_err(invalid)?;
+    interpolate(&mut value, env).map_err(invalid)?;
+
+    let config = deserialize_config(value, overrides).map_err(invalid)?;
+    config.validate()
+        .map_err(|e| invalid(format!("Configuração inválida: {}", e)))?;
+    Ok(config)
+}
+
+// Um campo sobrescrito por SYGMA_PROXY_*: nome da variável, caminho no YAML (`cache.max_capacity`)
+// e valor bruto. `untyped` = o campo não existia no YAML, então o tipo ainda é desconhecido.
+struct EnvOverride {
+    name: String,
+    path: String,
+    raw: String,
+    untyped: bool,
+}
+
+// SYGMA_PROXY_KERNEL_ADDRESS=... sobrescreve `kernel_address` (e substitui `kernel_backends`);
+// `__` desce nas seções e, com um número, nos itens já existentes de uma lista.
+// O valor só vira número/booleano quando o campo já é número/booleano no YAML;
+// nos demais casos fica string (ver `deserialize_config` para campos ausentes).
+fn apply_env_overrides(value: &mut Value, env: &HashMap<String, String>) -> Result<Vec<EnvOverride>, String> {
+    let mut overrides: Vec<_> = env.iter()
+        .filter_map(|(name, raw)| Some((name.strip_prefix(ENV_PREFIX)?.to_lowercase(), name, raw)))
+        .collect();
+    overrides.sort();
+
+    let mut applied = Vec::with_capacity(overrides.len());
+    for (path, name, raw) in overrides {
+        let keys: Vec<&str> = path.split("__").collect();
+        let (last, sections) = keys.split_last().unwrap();
+
+        let mut node = &mut *value;
+        for key in sections {
+            node = match node {
+                Value::Mapping(map) => map.entry(Value::from(*key)).or_insert_with(|| Value::Mapping(Mapping::new())),
+                Value::Sequence(items) => key.parse::<usize>().ok().and_then(|index| items.get_mut(index))
+                    .ok_or_else(|| format!("{}: '{}' não é um índice da lista", name, key))?,
+                _ => return Err(format!("{}: '{}' não é uma seção", name, key)),
+            };
+        }
+        let Value::Mapping(map) = node else {
+            return Err(format!("{}: destino não é uma seção", name));
+        };
+        let (scalar, untyped) = match map.get(*last) {
+            Some(Value::Bool(_) | Value::Number(_)) => (env_scalar(raw), false),
+            Some(Value::Null) | None => (Value::String(raw.clone()), true),
+            Some(_) => (Value::String(raw.clone()), false),
+        };
+        map.insert(Value::from(*last), scalar);
+        applied.push(EnvOverride { name: name.clone(), path: keys.join("."), raw: raw.clone(), untyped });
+    }
+    // Com a lista no YAML, um `kernel_address` vindo do ambiente seria ignorado por `Config::backends`
+    if applied.iter().any(|o| o.path == "kernel_address") {
+        if let Value::Mapping(map) = value {
+            map.remove("kernel_backends");
+        }
+    }
+    Ok(applied)
+}
+
+// Números e booleanos mantêm o tipo; todo o resto vira string.
+fn env_scalar(raw: &str) -> Value {
+    match serde_yaml::from_str::<Value>(raw) {
+        Ok(value @ (Value::Bool(_) | Value::Number(_))) => value,
+        _ => Value::String(raw.to_string()),
+    }
+}
+
+// Desserializa o YAML já com os overrides. Um override em campo ausente do YAML entra como
+// string; se a estrutura pedir número/booleano naquele caminho, ele é convertido e a leitura
+// repetida. Overrides que não correspondem a nenhum campo conhecido são recusados.
+fn deserialize_config(mut value: Value, mut overrides: Vec<EnvOverride>) -> Result<Config, String> {
+    loop {
+        let mut ignored = Vec::new();
+        let result = serde_path_to_error::deserialize::<_, Config>(serde_ignored::Deserializer::new(
+            value.clone(),
+            &mut |path: serde_ignored::Path| ignored.push(path.to_string().replace(".?", "")),
+        ));
+        let error = match result {
+            Ok(config) => {
+                if let Some(unknown) = overrides.iter().find(|o| {
+                    ignored.iter().any(|path| o.path == *path || o.path.starts_with(&format!("{}.", path)))
+                }) {
+                    return Err(format!("{}: campo desconhecido '{}'", unknown.name, unknown.path));
+                }
+                return Ok(config);
+            }
+            Err(error) => error,
+        };
+
+        // `kernel_backends[0].address` -> `kernel_backends.0.address`, o formato dos overrides
+        let path = error.path().to_string().replace('[', ".").replace(']', "");
+        let message = error.inner().to_string();
+        let blamed = overrides.iter().position(|o| {
+            o.untyped && (o.path == path
+                || (o.path.starts_with(&format!("{}.", path)) && message.contains(&format!("string {:?}", o.raw))))
+        });
+        let Some(index) = blamed else {
+            return Err(format!("Erro de parse YAML: {}: {}", path, message));
+        };
+        let retyped = overrides.swap_remove(index);
+        let scalar = env_scalar(&retyped.raw);
+        if scalar.is_string() {
+            return Err(format!("{}: {}", retyped.name, message));
+        }
+        set_path(&mut value, &retyped.path, scalar);
+    }
+}
+
+fn set_path(value: &mut Value, path: &str, scalar: Value) {
+    let mut node = value;
+    for key in path.split('.') {
+        node = match node {
+            Value::Mapping(map) => map.entry(Value::from(key)).or_insert(Value::Null),
+            Value::Sequence(items) => match key.parse::<usize>().ok().and_then(|index| items.get_mut(index)) {
+                Some(item) => item,
+                None => return,
+            },
+            _ => return,
+        };
+    }
+    *node = scalar;
+}
+
+// Expande `${VAR}` e `file:/caminho` em todas as strings, para que segredos
+// (chave HMAC, chaves TLS) não precisem ficar no config.yaml.
+fn interpolate(value: &mut Value, env: &HashMap<String, String>) -> Result<(), String> {
+    match value {
+        Value::String(text) => {
+            *text = match text.strip_prefix("file:") {
+                Some(path) => std::fs::read_to_string(path.trim())
+                    .map(|contents| contents.trim_end().to_string())
+                    .map_err(|e| format!("Falha ao ler segredo '{}': {}", path.trim(), e))?,
+                None => expand_env_vars(text, env)?,
+            };
+        }
+        Value::Sequence(items) => {
+            for item in items {
+                interpolate(item, env)?;
+            }
+        }
+        Value::Mapping(map) => {
+            for (_, item) in map.iter_mut() {
+                interpolate(item, env)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn expand_env_vars(text: &str, env: &HashMap<String, String>) -> Result<String, String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        let end = rest[start..].find('}')
+            .ok_or_else(|| format!("'${{' sem '}}' em '{}'", text))?;
+        let name = &rest[start + 2..start + end];
+        let resolved = env.get(name)
+            .ok_or_else(|| format!("Variável de ambiente '{}' não definida", name))?;
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(resolved);
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+// Relê e valida o arquivo. Em caso de erro, a configuração anterior continua ativa.
+pub fn reload_config(path: &Path) -> Result<(), io::Error> {
+    let new_config = load_config(path)?;
+    let old_config = current();
+
+    if *old_config == new_config {
+        info!("{} sem mudanças efetivas.", path.display());
+        return Ok(());
+    }
+    for section in old_config.restart_required_changes(&new_config) {
+        warn!("Mudança em '{}' só terá efeito após reiniciar o Proxy.", section);
+    }
+    // Nova chave HMAC: tokens aceitos com a chave anterior precisam ser reverificados
+    if old_config.auth != new_config.auth {
+        TRUST_CACHE.invalidate_all();
+        info!("Seção 'auth' alterada. TRUST_CACHE esvaziado.");
+    }
+    if old_config.logging.level != new_config.logging.level {
+        logging::set_level(&new_config.logging.level).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        info!("Nível de log alterado para '{}'.", new_config.logging.level);
+    }
+
+    *active().write().unwrap() = Arc::new(new_config);
+    info!("{} recarregado. Novas conexões usam a nova configuração.", path.display());
+    Ok(())
+}
+
+fn reload_and_report(path: &Path) {
+    if let Err(e) = reload_config(path) {
+        error!("Falha ao recarregar {} (configuração anterior mantida): {}", path.display(), e);
+    }
+}
+
+// Recarrega a configuração quando o arquivo muda ou quando o processo recebe SIGHUP.
+pub async fn watch_config(path: PathBuf, poll_interval: Duration) -> io::Result<()> {
+    let mut hangup = signal(SignalKind::hangup())?;
+    let mut last_signature = file_signature(&path);
+    let mut ticker = tokio::time::interval(poll_interval);
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                let signature = file_signature(&path);
+                if signature == last_signature {
+                    continue;
+                }
+                last_signature = signature;
+                info!("Mudança detectada em {}.", path.display());
+            }
+            _ = hangup.recv() => {
+                info!("SIGHUP recebido.");
+            }
+        }
+        reload_and_report(&path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESSES: &str = "proxy_address: \"127.0.0.1:7979\"\nkernel_address: \"127.0.0.1:8080\"\n";
+    // Mínimo válido: com replay.require_nonce (o padrão), auth.hmac_key é obrigatória
+    const BASE: &str = "proxy_address: \"127.0.0.1:7979\"\nkernel_address: \"127.0.0.1:8080\"\nauth:\n  hmac_key: \"chave\"\n";
+
+    fn no_env() -> HashMap<String, String> {
+        HashMap::new()
+    }
+
+    fn env(vars: &[(&str, &str)]) -> HashMap<String, String> {
+        vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    // Teste 1: Configuração mínima (sem seções opcionais) usa os padrões.
+    #[test]
+    fn test_parse_minimal_config() {
+        let config = parse_config(BASE, &no_env()).unwrap();
+        assert_eq!(config.cache, CacheConfig::default());
+        assert_eq!(config.revocation_file, None);
+    }
+
+    // Teste 2: Arquivos inválidos são recusados antes da troca.
+    #[test]
+    fn test_invalid_config_rejected() {
+        assert!(parse_config("proxy_address: [", &no_env()).is_err(), "YAML malformado deve falhar.");
+        assert!(parse_config("proxy_address: \"nao_e_endereco\"\nkernel_address: \"127.0.0.1:8080\"\n", &no_env()).is_err());
+        assert!(parse_config(&format!("{}lockout:\n  max_failures: 0\n", BASE), &no_env()).is_err());
+        assert!(parse_config(ADDRESSES, &no_env()).is_err(), "require_nonce sem hmac_key deve falhar.");
+        assert!(parse_config(&format!("{}replay:\n  require_nonce: false\n", ADDRESSES), &no_env()).is_ok());
+        let tiers = "rate_limit:\n  tiers:\n    gold: { capacity: 100, refill_per_sec: 50 }\n";
+        assert!(parse_config(&format!("{}replay:\n  require_nonce: false\n{}", ADDRESSES, tiers), &no_env()).is_err(), "tiers sem hmac_key deve falhar.");
+        assert!(parse_config(&format!("{}{}", BASE, tiers), &no_env()).is_ok());
+        let scopes = "policy:\n  scopes:\n    retail: { max_amount: 100 }\n";
+        assert!(parse_config(&format!("{}replay:\n  require_nonce: false\n{}", ADDRESSES, scopes), &no_env()).is_err(), "scopes sem hmac_key deve falhar.");
+        assert!(parse_config(&format!("{}{}", BASE, scopes), &no_env()).is_ok());
+
+        // Endereços unix:/caminho no listener e nos backends
+        assert!(parse_config("proxy_address: \"unix:/tmp/proxy.sock\"\nkernel_address: \"unix:/tmp/kernel.sock\"\nauth:\n  hmac_key: \"chave\"\n", &no_env()).is_ok());
+        assert!(parse_config("proxy_address: \"unix:\"\nkernel_address: \"127.0.0.1:8080\"\n", &no_env()).is_err());
+        assert!(parse_config(&format!("{}unix_socket:\n  mode: 0o400\n", BASE), &no_env()).is_err());
+    }
+
+    // Teste 3: Uma recarga com erro mantém a configuração anterior.
+    #[test]
+    fn test_failed_reload_keeps_previous_config() {
+        let before = current();
+        let path = std::env::temp_dir().join(format!("sygma_config_{}.yaml", std::process::id()));
+        std::fs::write(&path, "kernel_address: 42: [").unwrap();
+
+        assert!(reload_config(&path).is_err());
+        assert!(Arc::ptr_eq(&before, &current()), "A configuração ativa não pode mudar após falha.");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    // Teste 4: Variáveis SYGMA_PROXY_* sobrescrevem campos, inclusive em seções aninhadas.
+    #[test]
+    fn test_env_overrides() {
+        let vars = env(&[
+            ("SYGMA_PROXY_KERNEL_ADDRESS", "10.0.0.5:9090"),
+            ("SYGMA_PROXY_LOAD_BALANCING", "least_connections"),
+            ("SYGMA_PROXY_CACHE__MAX_CAPACITY", "500"),
+            ("SYGMA_PROXY_CACHE__USE_TOKEN_EXP", "false"),
+            ("OUTRA_VARIAVEL", "ignorada"),
+        ]);
+        let config = parse_config(BASE, &vars).unwrap();
+        assert_eq!(config.kernel_address.as_deref(), Some("10.0.0.5:9090"));
+        assert_eq!(config.load_balancing, Strategy::LeastConnections);
+        assert_eq!(config.cache.max_capacity, 500);
+        assert!(!config.cache.use_token_exp);
+    }
+
+    // Teste 5: Segredos vêm de ${VAR} ou file:, nunca do próprio YAML.
+    #[test]
+    fn test_secret_interpolation() {
+        let yaml = format!("{}auth:\n  hmac_key: \"${{SYGMA_TEST_KEY}}\"\n", ADDRESSES);
+        let config = parse_config(&yaml, &env(&[("SYGMA_TEST_KEY", "segredo")])).unwrap();
+        assert_eq!(config.auth.hmac_key.as_deref(), Some("segredo"));
+        assert!(!format!("{:?}", config).contains("segredo"), "A chave não pode aparecer no Debug.");
+        assert!(parse_config(&yaml, &no_env()).is_err(), "Variável ausente deve ser um erro.");
+
+        let key_path = std::env::temp_dir().join(format!("sygma_hmac_{}.key", std::process::id()));
+        std::fs::write(&key_path, "chave-do-arquivo\n").unwrap();
+        let yaml = format!("{}auth:\n  hmac_key: \"file:{}\"\n", ADDRESSES, key_path.display());
+        let config = parse_config(&yaml, &no_env()).unwrap();
+        assert_eq!(config.auth.hmac_key.as_deref(), Some("chave-do-arquivo"));
+        std::fs::remove_file(&key_path).unwrap();
+    }
+
+    // Teste 6: Valores com cara de número continuam string em campos string; nomes desconhecidos falham.
+    #[test]
+    fn test_env_override_types_and_unknown_keys() {
+        let vars = env(&[
+            ("SYGMA_PROXY_AUTH__HMAC_KEY", "123456"),
+            ("SYGMA_PROXY_REVOCATION_FILE", "2024"),
+            ("SYGMA_PROXY_POLICY__MAX_AMOUNT", "500"),
+        ]);
+        let config = parse_config(BASE, &vars).unwrap();
+        assert_eq!(config.auth.hmac_key.as_deref(), Some("123456"));
+        assert_eq!(config.revocation_file.as_deref(), Some("2024"));
+        assert_eq!(config.policy.limits.max_amount, Some(500));
+
+        let yaml = format!("{}cache:\n  max_capacity: 100\n", BASE);
+        assert!(parse_config(&yaml, &env(&[("SYGMA_PROXY_CACHE__MAX_CAPACITY", "muitos")])).is_err());
+
+        for name in ["SYGMA_PROXY_CACHE__MAX_CAPACIDADE", "SYGMA_PROXY_NAO_EXISTE__CAMPO", "SYGMA_PROXY_KERNEL_ADRESS"] {
+            let err = parse_config(BASE, &env(&[(name, "1")])).unwrap_err();
+            assert!(err.to_string().contains(name), "Override desconhecido deve ser recusado: {}", err);
+        }
+    }
+
+    // Teste 7: Com o config.yaml distribuído (lista `kernel_backends`), SYGMA_PROXY_KERNEL_ADDRESS
+    // substitui a lista e os itens podem ser sobrescritos pelo índice.
+    #[test]
+    fn test_env_overrides_shipped_backends() {
+        let shipped = include_str!("../config.yaml");
+        let backend = |address: &str, weight: u32| BackendConfig { address: address.to_string(), weight };
+
+        // Sem nenhuma variável de ambiente: o arquivo distribuído sobe como está
+        let config = parse_config(shipped, &no_env()).unwrap();
+        assert_eq!(config.backends(), vec![backend("127.0.0.1:8080", 1)]);
+
+        let config = parse_config(shipped, &env(&[("SYGMA_PROXY_KERNEL_ADDRESS", "10.0.0.5:9090")])).unwrap();
+        assert_eq!(config.backends(), vec![backend("10.0.0.5:9090", 1)]);
+
+        let vars = env(&[("SYGMA_PROXY_KERNEL_BACKENDS__0__ADDRESS", "10.0.0.6:9090"), ("SYGMA_PROXY_KERNEL_BACKENDS__0__WEIGHT", "3")]);
+        assert_eq!(parse_config(shipped, &vars).unwrap().backends(), vec![backend("10.0.0.6:9090", 3)]);
+
+        let err = parse_config(shipped, &env(&[("SYGMA_PROXY_KERNEL_BACKENDS__1__ADDRESS", "10.0.0.7:9090")])).unwrap_err();
+        assert!(err.to_string().contains("não é um índice"), "{}", err);
+    }
+}
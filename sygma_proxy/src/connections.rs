@@ -0,0 +1,367 @@
+// sygma_proxy/src/connections.rs - Timeouts de Conexão e Limites de Conexões Simultâneas
+//
+// Toda conexão aceita (TCP ou HTTP) passa por aqui:
+// 1. Limites: máximo de conexões abertas no total e por IP. Acima deles a conexão é fechada
+//    na hora (o Proxy descarta carga em vez de esgotar os descritores de arquivo).
+// 2. Relógio de timeouts sobre o socket, com fases:
+//    FirstByte --1º byte--> Request --requisição recebida--> Processing --resposta--> Idle --byte--> Request
+//    Em FirstByte, Request e Idle, uma leitura parada além do prazo encerra a conexão.
+//    Em Processing não há prazo de leitura (o Kernel tem os seus timeouts, ver kernel_pool.rs).
+// O handshake TLS tem prazo próprio, `handshake_timeout_ms`.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{Instant, Sleep};
+use tracing::debug;
+
+use crate::metrics::METRICS;
+
+// --- SEÇÃO `connections` DO config.yaml ---
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ConnectionConfig {
+    // Prazo do handshake TLS
+    pub handshake_timeout_ms: u64,
+    // Da conexão aceita ao primeiro byte do cliente
+    pub first_byte_timeout_ms: u64,
+    // Do primeiro byte à requisição completa (cabeçalhos e corpo, no HTTP)
+    pub request_timeout_ms: u64,
+    // Keep-alive HTTP: espera máxima pela próxima requisição
+    pub idle_timeout_secs: u64,
+    // Conexões abertas simultaneamente, somando os listeners TCP e HTTP
+    pub max_connections: usize,
+    pub max_connections_per_ip: usize,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        ConnectionConfig {
+            handshake_timeout_ms: 5_000,
+            first_byte_timeout_ms: 5_000,
+            request_timeout_ms: 10_000,
+            idle_timeout_secs: 60,
+            max_connections: 10_000,
+            max_connections_per_ip: 100,
+        }
+    }
+}
+
+impl ConnectionConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.handshake_timeout_ms == 0 || self.first_byte_timeout_ms == 0 || self.request_timeout_ms == 0 || self.idle_timeout_secs == 0 {
+            return Err("connections: os timeouts devem ser maiores que zero".to_string());
+        }
+        if self.max_connections == 0 || self.max_connections_per_ip == 0 {
+            return Err("connections.max_connections e max_connections_per_ip devem ser maiores que zero".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn handshake_timeout(&self) -> Duration {
+        Duration::from_millis(self.handshake_timeout_ms)
+    }
+}
+
+// --- LIMITES DE CONEXÕES ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    GlobalLimit,
+    IpLimit,
+}
+
+impl Rejection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rejection::GlobalLimit => "global_limit",
+            Rejection::IpLimit => "ip_limit",
+        }
+    }
+}
+
+pub struct ConnectionLimiter {
+    total: AtomicUsize,
+    per_ip: Mutex<HashMap<IpAddr, usize>>,
+}
+
+// Vaga ocupada por uma conexão aberta; liberada quando a conexão termina
+pub struct ConnectionSlot<'a> {
+    limiter: &'a ConnectionLimiter,
+    ip: IpAddr,
+}
+
+impl Drop for ConnectionSlot<'_> {
+    fn drop(&mut self) {
+        self.limiter.total.fetch_sub(1, Ordering::Relaxed);
+        let mut per_ip = self.limiter.per_ip.lock().unwrap();
+        if let Some(count) = per_ip.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                per_ip.remove(&self.ip);
+            }
+        }
+        METRICS.set_open_connections(self.limiter.total.load(Ordering::Relaxed));
+    }
+}
+
+impl ConnectionLimiter {
+    pub fn new() -> Self {
+        ConnectionLimiter { total: AtomicUsize::new(0), per_ip: Mutex::new(HashMap::new()) }
+    }
+
+    // Os limites vêm do snapshot da configuração (valem na hora após um hot reload)
+    pub fn try_acquire(&self, ip: IpAddr, config: &ConnectionConfig) -> Result<ConnectionSlot<'_>, Rejection> {
+        let mut per_ip = self.per_ip.lock().unwrap();
+        if self.total.load(Ordering::Relaxed) >= config.max_connections {
+            return Err(Rejection::GlobalLimit);
+        }
+        let count = per_ip.entry(ip).or_insert(0);
+        if *count >= config.max_connections_per_ip {
+            return Err(Rejection::IpLimit);
+        }
+        *count += 1;
+        let total = self.total.fetch_add(1, Ordering::Relaxed) + 1;
+        METRICS.set_open_connections(total);
+        Ok(ConnectionSlot { limiter: self, ip })
+    }
+}
+
+impl Default for ConnectionLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+lazy_static! {
+    pub static ref LIMITER: ConnectionLimiter = ConnectionLimiter::new();
+}
+
+// Admissão de uma conexão recém-aceita: vaga nos limites + relógio de timeouts.
+// `None`: recusada (o chamador só fecha o socket, sem gastar mais nada com ela).
+pub fn admit(ip: IpAddr, config: &ConnectionConfig) -> Option<(ConnectionSlot<'static>, Arc<ConnectionTimer>)> {
+    match LIMITER.try_acquire(ip, config) {
+        Ok(slot) => Some((slot, ConnectionTimer::new(config))),
+        Err(rejection) => {
+            // Em debug: sob ataque, um log por conexão recusada só amplificaria a carga
+            debug!("Conexão de {} recusada: {}.", ip, rejection.as_str());
+            METRICS.record_connection_rejected(rejection.as_str());
+            None
+        }
+    }
+}
+
+// --- RELÓGIO DE TIMEOUTS ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    FirstByte,
+    Request,
+    Processing,
+    Idle,
+}
+
+impl Phase {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Phase::FirstByte => "first_byte",
+            Phase::Request => "request",
+            Phase::Processing => "processing",
+            Phase::Idle => "idle",
+        }
+    }
+}
+
+struct TimerState {
+    phase: Phase,
+    deadline: Option<Instant>,
+}
+
+// Compartilhado entre o socket (TimedStream) e quem trata a requisição
+pub struct ConnectionTimer {
+    config: ConnectionConfig,
+    state: Mutex<TimerState>,
+}
+
+impl ConnectionTimer {
+    pub fn new(config: &ConnectionConfig) -> Arc<Self> {
+        let deadline = Instant::now() + Duration::from_millis(config.first_byte_timeout_ms);
+        Arc::new(ConnectionTimer {
+            config: config.clone(),
+            state: Mutex::new(TimerState { phase: Phase::FirstByte, deadline: Some(deadline) }),
+        })
+    }
+
+    // A requisição chegou ao tratamento: suspende o prazo de leitura e devolve o prazo
+    // restante para o que ainda falta ler (o corpo, no HTTP)
+    pub fn processing(&self) -> Instant {
+        let mut state = self.state.lock().unwrap();
+        let deadline = match (state.phase, state.deadline) {
+            (Phase::Request, Some(deadline)) => deadline,
+            _ => Instant::now() + Duration::from_millis(self.config.request_timeout_ms),
+        };
+        *state = TimerState { phase: Phase::Processing, deadline: None };
+        deadline
+    }
+
+    fn on_read(&self) {
+        let mut state = self.state.lock().unwrap();
+        if matches!(state.phase, Phase::FirstByte | Phase::Idle) {
+            let deadline = Instant::now() + Duration::from_millis(self.config.request_timeout_ms);
+            *state = TimerState { phase: Phase::Request, deadline: Some(deadline) };
+        }
+    }
+
+    fn on_write(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.phase == Phase::Processing {
+            let deadline = Instant::now() + Duration::from_secs(self.config.idle_timeout_secs);
+            *state = TimerState { phase: Phase::Idle, deadline: Some(deadline) };
+        }
+    }
+
+    fn deadline(&self) -> Option<(Phase, Instant)> {
+        let state = self.state.lock().unwrap();
+        state.deadline.map(|deadline| (state.phase, deadline))
+    }
+}
+
+// Socket com o relógio de timeouts aplicado às leituras
+pub struct TimedStream<S> {
+    inner: S,
+    timer: Arc<ConnectionTimer>,
+    sleep: Pin<Box<Sleep>>,
+}
+
+impl<S> TimedStream<S> {
+    pub fn new(inner: S, timer: Arc<ConnectionTimer>) -> Self {
+        let sleep = Box::pin(tokio::time::sleep_until(Instant::now()));
+        TimedStream { inner, timer, sleep }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for TimedStream<S> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        match Pin::new(&mut self.inner).poll_read(cx, buf) {
+            Poll::Ready(Ok(())) => {
+                if buf.filled().len() > filled {
+                    self.timer.on_read();
+                }
+                Poll::Ready(Ok(()))
+            }
+            Poll::Pending => {
+                let Some((phase, deadline)) = self.timer.deadline() else {
+                    return Poll::Pending;
+                };
+                if self.sleep.deadline() != deadline {
+                    self.sleep.as_mut().reset(deadline);
+                }
+                match self.sleep.as_mut().poll(cx) {
+                    Poll::Ready(()) => {
+                        METRICS.record_connection_timeout(phase.as_str());
+                        let message = format!("Timeout da conexão na fase {}", phase.as_str());
+                        Poll::Ready(Err(io::Error::new(io::ErrorKind::TimedOut, message)))
+                    }
+                    Poll::Pending => Poll::Pending,
+                }
+            }
+            other => other,
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for TimedStream<S> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let written = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if matches!(written, Poll::Ready(Ok(n)) if n > 0) {
+            self.timer.on_write();
+        }
+        written
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn config() -> ConnectionConfig {
+        ConnectionConfig {
+            first_byte_timeout_ms: 50,
+            request_timeout_ms: 50,
+            idle_timeout_secs: 1,
+            max_connections: 3,
+            max_connections_per_ip: 2,
+            ..ConnectionConfig::default()
+        }
+    }
+
+    // Teste 1: Limites global e por IP; a vaga volta quando a conexão termina.
+    #[test]
+    fn test_connection_limits() {
+        let limiter = ConnectionLimiter::new();
+        let config = config();
+        let a: IpAddr = "10.0.0.1".parse().unwrap();
+        let b: IpAddr = "10.0.0.2".parse().unwrap();
+
+        let first = limiter.try_acquire(a, &config).unwrap();
+        let _second = limiter.try_acquire(a, &config).unwrap();
+        assert_eq!(limiter.try_acquire(a, &config).err(), Some(Rejection::IpLimit));
+        let _third = limiter.try_acquire(b, &config).unwrap();
+        assert_eq!(limiter.try_acquire(b, &config).err(), Some(Rejection::GlobalLimit));
+
+        drop(first);
+        assert!(limiter.try_acquire(a, &config).is_ok(), "A vaga liberada pode ser reutilizada.");
+    }
+
+    // Teste 2: Cliente que conecta e não envia nada é desconectado após o prazo do primeiro byte.
+    #[tokio::test]
+    async fn test_first_byte_timeout() {
+        let (client, server) = tokio::io::duplex(64);
+        let mut stream = TimedStream::new(server, ConnectionTimer::new(&config()));
+        let mut buffer = [0; 16];
+
+        let error = stream.read(&mut buffer).await.expect_err("Sem dados, a leitura deve expirar.");
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        drop(client);
+    }
+
+    // Teste 3: Durante o processamento não há prazo de leitura; depois da resposta vale o idle.
+    #[tokio::test]
+    async fn test_phases() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let timer = ConnectionTimer::new(&config());
+        let mut stream = TimedStream::new(server, timer.clone());
+        let mut buffer = [0; 16];
+
+        client.write_all(b"GET").await.unwrap();
+        assert_eq!(stream.read(&mut buffer).await.unwrap(), 3);
+        assert_eq!(timer.deadline().map(|(phase, _)| phase), Some(Phase::Request));
+
+        timer.processing();
+        assert!(timer.deadline().is_none());
+        assert!(tokio::time::timeout(Duration::from_millis(100), stream.read(&mut buffer)).await.is_err(),
+            "Em processamento a leitura espera sem expirar.");
+
+        stream.write_all(b"200").await.unwrap();
+        assert_eq!(timer.deadline().map(|(phase, _)| phase), Some(Phase::Idle));
+    }
+}
@@ -0,0 +1,290 @@
+// sygma_proxy/src/health.rs - Monitor de Saúde do Kernel (T1) com Circuit Breaker
+//
+// CLOSED:    requisições passam; falhas consecutivas (sondas ou requisições) abrem o circuito.
+// OPEN:      requisições falham rápido com 503 + Retry-After até o fim de `open_duration_secs`.
+// HALF_OPEN: algumas requisições de teste passam; o resultado delas fecha ou reabre o circuito.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use tracing::warn;
+
+// --- SEÇÃO `health` DO config.yaml ---
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct HealthConfig {
+    // Intervalo entre os PINGs de aplicação enviados ao Kernel em segundo plano
+    pub probe_interval_ms: u64,
+    pub probe_timeout_ms: u64,
+    // Falhas consecutivas que abrem o circuito
+    pub failure_threshold: u32,
+    // Tempo em OPEN antes de liberar requisições de teste (também é o Retry-After)
+    pub open_duration_secs: u64,
+    // Requisições de teste simultâneas em HALF_OPEN
+    pub half_open_max_trials: u32,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        HealthConfig {
+            probe_interval_ms: 2_000,
+            probe_timeout_ms: 1_000,
+            failure_threshold: 3,
+            open_duration_secs: 10,
+            half_open_max_trials: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl fmt::Display for BreakerState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BreakerState::Closed => "CLOSED",
+            BreakerState::Open => "OPEN",
+            BreakerState::HalfOpen => "HALF_OPEN",
+        })
+    }
+}
+
+// Como a requisição foi admitida: o resultado de uma requisição de teste decide a recuperação.
+// A vaga de teste volta ao HALF_OPEN no `record` ou quando a admissão é descartada sem ele
+// (future cancelado pela desconexão do cliente); sem isso o circuito ficaria preso em HALF_OPEN.
+#[derive(Debug)]
+pub struct Admission {
+    trial: Option<Trial>,
+}
+
+#[cfg(test)]
+impl Admission {
+    fn is_trial(&self) -> bool {
+        self.trial.is_some()
+    }
+}
+
+#[derive(Debug)]
+struct Trial {
+    inner: Arc<Mutex<BreakerInner>>,
+    // HALF_OPEN em que a vaga foi tomada; uma vaga de um HALF_OPEN anterior não conta mais
+    epoch: u64,
+}
+
+impl Drop for Trial {
+    fn drop(&mut self) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.state == BreakerState::HalfOpen && inner.half_open_epoch == self.epoch {
+            inner.trials_in_flight = inner.trials_in_flight.saturating_sub(1);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BreakerInner {
+    state: BreakerState,
+    consecutive_failures: u32,
+    opened_at: Instant,
+    trials_in_flight: u32,
+    half_open_epoch: u64,
+}
+
+pub struct CircuitBreaker {
+    config: HealthConfig,
+    inner: Arc<Mutex<BreakerInner>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: HealthConfig) -> Self {
+        CircuitBreaker {
+            config,
+            inner: Arc::new(Mutex::new(BreakerInner {
+                state: BreakerState::Closed,
+                consecutive_failures: 0,
+                opened_at: Instant::now(),
+                trials_in_flight: 0,
+                half_open_epoch: 0,
+            })),
+        }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        self.inner.lock().unwrap().state
+    }
+
+    fn open_duration(&self) -> Duration {
+        Duration::from_secs(self.config.open_duration_secs)
+    }
+
+    fn transition(&self, inner: &mut BreakerInner, to: BreakerState, reason: &str) {
+        if inner.state == to {
+            return;
+        }
+        warn!("Circuit breaker {} -> {} ({}).", inner.state, to, reason);
+        inner.state = to;
+        inner.trials_in_flight = 0;
+        match to {
+            BreakerState::Open => inner.opened_at = Instant::now(),
+            BreakerState::Closed => inner.consecutive_failures = 0,
+            BreakerState::HalfOpen => inner.half_open_epoch += 1,
+        }
+    }
+
+    // Decide se a requisição pode seguir para o Kernel. `Err` traz o Retry-After sugerido.
+    pub fn admit(&self) -> Result<Admission, Duration> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.state == BreakerState::Open {
+            let elapsed = inner.opened_at.elapsed();
+            if elapsed < self.open_duration() {
+                return Err(self.open_duration() - elapsed);
+            }
+            self.transition(&mut inner, BreakerState::HalfOpen, "tempo de espera encerrado");
+        }
+
+        match inner.state {
+            BreakerState::Closed => Ok(Admission { trial: None }),
+            BreakerState::HalfOpen if inner.trials_in_flight < self.config.half_open_max_trials.max(1) => {
+                inner.trials_in_flight += 1;
+                Ok(Admission { trial: Some(Trial { inner: self.inner.clone(), epoch: inner.half_open_epoch }) })
+            }
+            // Testes já em andamento: o cliente tenta de novo em instantes
+            _ => Err(Duration::from_secs(1)),
+        }
+    }
+
+    // Resultado de uma requisição real (falha = Kernel inacessível ou caiu no meio do Settlement)
+    // A vaga de teste é devolvida quando `admission` sai de escopo, depois da transição
+    pub fn record(&self, admission: Admission, success: bool) {
+        let mut inner = self.inner.lock().unwrap();
+
+        if let Some(trial) = &admission.trial {
+            if inner.state != BreakerState::HalfOpen || inner.half_open_epoch != trial.epoch {
+                return;
+            }
+            if success {
+                self.transition(&mut inner, BreakerState::Closed, "requisição de teste bem-sucedida");
+            } else {
+                self.transition(&mut inner, BreakerState::Open, "requisição de teste falhou");
+            }
+            return;
+        }
+        self.record_outcome(&mut inner, success);
+    }
+
+    // Resultado de uma sonda de saúde em segundo plano
+    pub fn record_probe(&self, success: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.state {
+            // Em HALF_OPEN só as requisições de teste fecham o circuito; uma sonda falha o reabre
+            BreakerState::HalfOpen if !success => {
+                self.transition(&mut inner, BreakerState::Open, "sonda de saúde falhou");
+            }
+            BreakerState::Closed => self.record_outcome(&mut inner, success),
+            _ => {}
+        }
+    }
+
+    fn record_outcome(&self, inner: &mut BreakerInner, success: bool) {
+        if inner.state != BreakerState::Closed {
+            return;
+        }
+        if success {
+            inner.consecutive_failures = 0;
+            return;
+        }
+        inner.consecutive_failures += 1;
+        if inner.consecutive_failures >= self.config.failure_threshold.max(1) {
+            let reason = format!("{} falhas consecutivas", inner.consecutive_failures);
+            self.transition(inner, BreakerState::Open, &reason);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(open_duration_secs: u64) -> CircuitBreaker {
+        CircuitBreaker::new(HealthConfig { failure_threshold: 2, open_duration_secs, ..HealthConfig::default() })
+    }
+
+    // Teste 1: Falhas consecutivas abrem o circuito e as requisições falham rápido.
+    #[test]
+    fn test_opens_after_threshold() {
+        let breaker = breaker(30);
+        breaker.record_probe(false);
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        breaker.record(breaker.admit().unwrap(), false);
+        assert_eq!(breaker.state(), BreakerState::Open);
+
+        let retry_after = breaker.admit().expect_err("Circuito aberto deve falhar rápido");
+        assert!(retry_after <= Duration::from_secs(30));
+    }
+
+    // Teste 2: Em HALF_OPEN, a requisição de teste decide a recuperação.
+    #[test]
+    fn test_half_open_trial_closes_or_reopens() {
+        let breaker = breaker(0);
+        breaker.record_probe(false);
+        breaker.record_probe(false);
+        assert_eq!(breaker.state(), BreakerState::Open);
+
+        let trial = breaker.admit().unwrap();
+        assert!(trial.is_trial());
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        assert!(breaker.admit().is_err(), "Só uma requisição de teste por vez.");
+
+        breaker.record(trial, false);
+        assert_eq!(breaker.state(), BreakerState::Open, "Teste falho reabre o circuito.");
+
+        let trial = breaker.admit().unwrap();
+        breaker.record(trial, true);
+        assert_eq!(breaker.state(), BreakerState::Closed, "Teste bem-sucedido fecha o circuito.");
+        assert!(!breaker.admit().unwrap().is_trial());
+    }
+
+    // Teste 3: Sucesso zera a contagem de falhas consecutivas.
+    #[test]
+    fn test_success_resets_failures() {
+        let breaker = breaker(30);
+        breaker.record_probe(false);
+        breaker.record_probe(true);
+        breaker.record_probe(false);
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+
+    // Teste 4: Requisição de teste cancelada (sem `record`) devolve a vaga; o HALF_OPEN não trava.
+    #[test]
+    fn test_cancelled_trial_releases_slot() {
+        let breaker = breaker(0);
+        breaker.record_probe(false);
+        breaker.record_probe(false);
+
+        let trial = breaker.admit().unwrap();
+        assert!(breaker.admit().is_err());
+        drop(trial);
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+
+        let trial = breaker.admit().expect("A vaga do teste cancelado deve voltar.");
+        breaker.record(trial, true);
+        assert_eq!(breaker.state(), BreakerState::Closed);
+
+        // Vaga de um HALF_OPEN anterior não mexe na contagem do atual
+        breaker.record_probe(false);
+        breaker.record_probe(false);
+        let stale = breaker.admit().unwrap();
+        breaker.record_probe(false);
+        let current = breaker.admit().unwrap();
+        drop(stale);
+        assert!(breaker.admit().is_err(), "O teste em andamento continua ocupando a vaga.");
+        breaker.record(current, true);
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+}
@@ -0,0 +1,158 @@
+// sygma_proxy/src/idempotency.rs - Chaves de Idempotência (retentativas seguras)
+//
+// O cliente envia uma chave por transação: terceiro campo no TCP (`token|payload|chave`) ou o
+// cabeçalho Idempotency-Key no HTTP. A primeira requisição com a chave segue o pipeline; as
+// repetidas dentro de `window_secs` recebem a resposta original sem chegar ao Kernel, ou um
+// 409 enquanto a primeira ainda está em andamento. As chaves são por subject.
+//
+// Só respostas do Kernel (200, 422 e o 502 de resultado incerto) e o 202 de um Settlement posto
+// na fila store-and-forward ficam guardadas: a repetição recebe o mesmo ticket. Rejeições
+// anteriores ao encaminhamento liberam a chave, e o cliente pode tentar de novo.
+
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use moka::sync::Cache;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+const MAX_KEY_LEN: usize = 128;
+
+// --- SEÇÃO `idempotency` DO config.yaml ---
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct IdempotencyConfig {
+    // Por quanto tempo a resposta de uma chave é lembrada
+    pub window_secs: u64,
+    pub max_keys: u64,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        IdempotencyConfig { window_secs: 86_400, max_keys: 100_000 }
+    }
+}
+
+// Letras, dígitos e -_.:, até 128 caracteres (UUIDs, ULIDs, hashes...)
+pub fn valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= MAX_KEY_LEN
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+struct Record {
+    payload_sha256: [u8; 32],
+    // Vazio enquanto a primeira requisição está em andamento
+    response: OnceLock<String>,
+}
+
+pub enum Begin<'a> {
+    // Primeira requisição com a chave: seguir o pipeline e chamar `Pending::complete`
+    New(Pending<'a>),
+    // Repetição: a resposta original
+    Replay(String),
+    InProgress,
+    // A chave já foi usada com outro payload
+    Mismatch,
+}
+
+// Chave reservada pela requisição em andamento. Sem `complete`, a chave é liberada.
+pub struct Pending<'a> {
+    store: &'a IdempotencyStore,
+    key: (String, String),
+    record: Option<Arc<Record>>,
+}
+
+impl Pending<'_> {
+    pub fn complete(mut self, response: &str) {
+        let Some(record) = self.record.take() else {
+            return;
+        };
+        if matches!(response.get(..3), Some("200" | "202" | "422" | "502")) {
+            let _ = record.response.set(response.to_string());
+        } else {
+            self.store.records.invalidate(&self.key);
+        }
+    }
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        if self.record.take().is_some() {
+            self.store.records.invalidate(&self.key);
+        }
+    }
+}
+
+pub struct IdempotencyStore {
+    // (subject, chave) -> payload e resposta
+    records: Cache<(String, String), Arc<Record>>,
+}
+
+impl IdempotencyStore {
+    pub fn new(config: &IdempotencyConfig) -> Self {
+        IdempotencyStore {
+            records: Cache::builder()
+                .max_capacity(config.max_keys)
+                .time_to_live(Duration::from_secs(config.window_secs))
+                .build(),
+        }
+    }
+
+    pub fn begin(&self, subject: &str, key: &str, payload: &str) -> Begin<'_> {
+        let key = (subject.to_string(), key.to_string());
+        let payload_sha256: [u8; 32] = Sha256::digest(payload.as_bytes()).into();
+        let entry = self.records.entry(key.clone())
+            .or_insert_with(|| Arc::new(Record { payload_sha256, response: OnceLock::new() }));
+        if entry.is_fresh() {
+            return Begin::New(Pending { store: self, key, record: Some(entry.into_value()) });
+        }
+
+        let record = entry.into_value();
+        if record.payload_sha256 != payload_sha256 {
+            return Begin::Mismatch;
+        }
+        match record.response.get() {
+            Some(response) => Begin::Replay(response.clone()),
+            None => Begin::InProgress,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> IdempotencyStore {
+        IdempotencyStore::new(&IdempotencyConfig::default())
+    }
+
+    // Teste 1: A repetição recebe a resposta original; durante a primeira, um 409.
+    #[test]
+    fn test_replay_original_response() {
+        let store = store();
+        let Begin::New(pending) = store.begin("conta42", "k1", "ZKP_HASH_S:1_R:2_A:3") else {
+            panic!("A primeira requisição deve seguir o pipeline.");
+        };
+        assert!(matches!(store.begin("conta42", "k1", "ZKP_HASH_S:1_R:2_A:3"), Begin::InProgress));
+        assert!(matches!(store.begin("conta43", "k1", "ZKP_HASH_S:1_R:2_A:3"), Begin::New(_)), "Chaves são por subject.");
+
+        pending.complete("200 OK: Payload liquidado");
+        assert!(matches!(store.begin("conta42", "k1", "ZKP_HASH_S:1_R:2_A:3"), Begin::Replay(response) if response == "200 OK: Payload liquidado"));
+        assert!(matches!(store.begin("conta42", "k1", "ZKP_HASH_S:1_R:2_A:4"), Begin::Mismatch));
+    }
+
+    // Teste 2: Rejeições antes do Kernel (e requisições abandonadas) liberam a chave.
+    #[test]
+    fn test_key_released_when_not_forwarded() {
+        let store = store();
+        let Begin::New(pending) = store.begin("conta42", "k2", "p") else { panic!() };
+        pending.complete("503 SERVICE UNAVAILABLE: Kernel T1 Offline");
+        let Begin::New(pending) = store.begin("conta42", "k2", "p") else { panic!("503 não deve ser guardado.") };
+        drop(pending);
+        assert!(matches!(store.begin("conta42", "k2", "p"), Begin::New(_)));
+
+        assert!(valid_key("3f1c6344-aa01-4b8e-9c1d-7e0f00000001"));
+        assert!(!valid_key("") && !valid_key("com espaço") && !valid_key(&"k".repeat(129)));
+    }
+}
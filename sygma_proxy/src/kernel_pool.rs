@@ -0,0 +1,495 @@
+// sygma_proxy/src/kernel_pool.rs - Pool de Conexões Persistentes e Multiplexadas com o Kernel (T1)
+//
+// Cada conexão aceita várias requisições simultâneas: as linhas levam um <id> e o Kernel
+// responde com o mesmo <id>, em qualquer ordem (ver sygma_kernel/src/server.rs).
+// Há um pool por backend do Kernel (ver balancer.rs), em host:porta ou unix:/caminho.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, WriteHalf};
+use tokio::sync::{oneshot, OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::unix_socket::{self, Connection};
+
+// --- SEÇÃO `kernel_pool` DO config.yaml ---
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct PoolConfig {
+    // Máximo de conexões abertas com o Kernel
+    pub max_size: usize,
+    // Requisições simultâneas numa conexão antes de abrir outra
+    pub max_in_flight_per_connection: usize,
+    // Conexões ociosas por mais tempo que isto são fechadas
+    pub idle_timeout_secs: u64,
+    // Conexões ociosas por mais tempo que isto recebem um PING antes de serem reutilizadas
+    pub validate_after_idle_secs: u64,
+    pub connect_timeout_ms: u64,
+    pub request_timeout_ms: u64,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            max_size: 4,
+            max_in_flight_per_connection: 16,
+            idle_timeout_secs: 60,
+            validate_after_idle_secs: 5,
+            connect_timeout_ms: 1_000,
+            request_timeout_ms: 5_000,
+        }
+    }
+}
+
+// Espera entre tentativas de checkout quando o pool está cheio e nenhuma conexão está pronta
+const CHECKOUT_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+fn timed_out(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, format!("Timeout: {}", what))
+}
+
+// Falha de uma requisição ao Kernel. A distinção importa para o failover:
+// uma linha que nunca chegou ao Kernel pode ser reenviada a outro backend com segurança.
+#[derive(Debug)]
+pub enum RequestError {
+    // Nenhum byte da requisição chegou ao socket
+    NotSent(io::Error),
+    // A requisição foi enviada (ao menos em parte), mas a resposta não chegou: o Kernel pode tê-la executado
+    Lost(io::Error),
+}
+
+impl RequestError {
+    pub fn was_sent(&self) -> bool {
+        matches!(self, RequestError::Lost(_))
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::NotSent(e) => write!(f, "requisição não enviada: {}", e),
+            RequestError::Lost(e) => write!(f, "requisição enviada sem resposta: {}", e),
+        }
+    }
+}
+
+// --- CONEXÃO MULTIPLEXADA ---
+pub struct KernelConnection {
+    writer: tokio::sync::Mutex<WriteHalf<Box<dyn Connection>>>,
+    pending: Arc<Mutex<HashMap<u64, oneshot::Sender<String>>>>,
+    next_request_id: AtomicU64,
+    in_flight: AtomicUsize,
+    last_used: Mutex<Instant>,
+    closed: Arc<AtomicBool>,
+    reader_task: JoinHandle<()>,
+    // Vaga no pool, liberada quando a conexão é descartada
+    _permit: OwnedSemaphorePermit,
+}
+
+impl KernelConnection {
+    async fn connect(address: &str, timeout: Duration, permit: OwnedSemaphorePermit) -> io::Result<Self> {
+        let stream = tokio::time::timeout(timeout, unix_socket::connect(address)).await
+            .map_err(|_| timed_out("conexão com o Kernel"))??;
+        Ok(Self::from_stream(stream, permit))
+    }
+
+    fn from_stream(stream: Box<dyn Connection>, permit: OwnedSemaphorePermit) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+
+        let pending: Arc<Mutex<HashMap<u64, oneshot::Sender<String>>>> = Arc::new(Mutex::new(HashMap::new()));
+        let closed = Arc::new(AtomicBool::new(false));
+
+        // Leitor: entrega cada resposta à requisição com o mesmo id
+        let reader_task = {
+            let pending = pending.clone();
+            let closed = closed.clone();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(reader).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let Some((id, body)) = line.split_once(' ') else { continue };
+                    let Ok(id) = id.parse::<u64>() else { continue };
+                    if let Some(sender) = pending.lock().unwrap().remove(&id) {
+                        let _ = sender.send(body.to_string());
+                    }
+                }
+                // EOF ou erro: a conexão morreu; as requisições pendentes falham
+                closed.store(true, Ordering::SeqCst);
+                pending.lock().unwrap().clear();
+            })
+        };
+
+        KernelConnection {
+            writer: tokio::sync::Mutex::new(writer),
+            pending,
+            next_request_id: AtomicU64::new(1),
+            in_flight: AtomicUsize::new(0),
+            last_used: Mutex::new(Instant::now()),
+            closed,
+            reader_task,
+            _permit: permit,
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    fn idle_for(&self) -> Duration {
+        if self.in_flight.load(Ordering::SeqCst) > 0 {
+            return Duration::ZERO;
+        }
+        self.last_used.lock().unwrap().elapsed()
+    }
+
+    // Envia um comando ("PING", "SETTLE <payload>") e aguarda a resposta correspondente
+    pub async fn request(&self, command: &str, timeout: Duration) -> Result<String, RequestError> {
+        if self.is_closed() {
+            return Err(RequestError::NotSent(io::Error::new(io::ErrorKind::BrokenPipe, "Conexão com o Kernel fechada")));
+        }
+
+        let id = self.next_request_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, sender);
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let _in_flight = InFlight { conn: self, id };
+
+        // A conexão só é fechada por falha de I/O (escrita em send_and_wait, EOF no leitor):
+        // esperar a vez de escrever ou a resposta além do prazo não a inutiliza para as demais
+        self.send_and_wait(id, command, receiver, timeout).await
+    }
+
+    async fn send_and_wait(&self, id: u64, command: &str, receiver: oneshot::Receiver<String>, timeout: Duration) -> Result<String, RequestError> {
+        let line = format!("{} {}\n", id, command);
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        // Só a espera pela vez de escrever é segura para failover: nada foi para o socket
+        let mut writer = tokio::time::timeout_at(deadline, self.writer.lock())
+            .await
+            .map_err(|_| RequestError::NotSent(timed_out("envio ao Kernel")))?;
+        if self.is_closed() {
+            return Err(RequestError::NotSent(io::Error::new(io::ErrorKind::BrokenPipe, "Conexão com o Kernel fechada")));
+        }
+
+        // Future descartado no meio da escrita: a linha pode ter ficado pela metade no socket
+        let mut partial_write = ClosedUnlessFinished { closed: &self.closed, finished: false };
+        let bytes = line.as_bytes();
+        let mut written = 0;
+        while written < bytes.len() {
+            let error = match tokio::time::timeout_at(deadline, writer.write(&bytes[written..])).await {
+                Ok(Ok(0)) => io::Error::new(io::ErrorKind::WriteZero, "Kernel não aceitou a linha"),
+                Ok(Ok(n)) => {
+                    written += n;
+                    continue;
+                }
+                Ok(Err(e)) => e,
+                Err(_) => timed_out("envio ao Kernel"),
+            };
+            // Um fragmento da linha pode estar no socket: a conexão não serve para mais nada
+            // (a próxima linha seria colada a ele) e, se algo foi escrito, o Kernel pode ter visto.
+            self.closed.store(true, Ordering::SeqCst);
+            let _ = writer.shutdown().await;
+            return Err(if written == 0 { RequestError::NotSent(error) } else { RequestError::Lost(error) });
+        }
+        partial_write.finished = true;
+        drop(writer);
+
+        tokio::time::timeout_at(deadline, receiver)
+            .await
+            .map_err(|_| RequestError::Lost(timed_out("resposta do Kernel")))?
+            .map_err(|_| RequestError::Lost(io::Error::new(io::ErrorKind::ConnectionReset, "Kernel encerrou a conexão")))
+    }
+
+    async fn ping(&self, timeout: Duration) -> bool {
+        matches!(self.request("PING", timeout).await.as_deref(), Ok("PONG"))
+    }
+}
+
+// Requisição registrada na conexão; o registro é desfeito mesmo quando o future é descartado
+// (cliente HTTP desconectado, timeout externo), sem vazar a entrada em `pending` e a contagem
+struct InFlight<'a> {
+    conn: &'a KernelConnection,
+    id: u64,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.conn.pending.lock().unwrap().remove(&self.id);
+        self.conn.in_flight.fetch_sub(1, Ordering::SeqCst);
+        *self.conn.last_used.lock().unwrap() = Instant::now();
+    }
+}
+
+struct ClosedUnlessFinished<'a> {
+    closed: &'a AtomicBool,
+    finished: bool,
+}
+
+impl Drop for ClosedUnlessFinished<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.closed.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Drop for KernelConnection {
+    fn drop(&mut self) {
+        self.reader_task.abort();
+    }
+}
+
+// --- POOL LIMITADO (um por backend) ---
+pub struct KernelPool {
+    address: String,
+    config: PoolConfig,
+    connections: Mutex<Vec<Arc<KernelConnection>>>,
+    slots: Arc<Semaphore>,
+}
+
+impl KernelPool {
+    pub fn new(address: &str, config: PoolConfig) -> Self {
+        let max_size = config.max_size.max(1);
+        KernelPool {
+            address: address.to_string(),
+            config,
+            connections: Mutex::new(Vec::new()),
+            slots: Arc::new(Semaphore::new(max_size)),
+        }
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_millis(self.config.request_timeout_ms)
+    }
+
+    pub fn size(&self) -> usize {
+        self.connections.lock().unwrap().len()
+    }
+
+    // Remove conexões fechadas ou ociosas demais
+    fn prune(&self) {
+        let idle_timeout = Duration::from_secs(self.config.idle_timeout_secs);
+        self.connections.lock().unwrap().retain(|conn| !conn.is_closed() && conn.idle_for() < idle_timeout);
+    }
+
+    pub fn evict_idle(&self) {
+        let before = self.size();
+        self.prune();
+        let evicted = before.saturating_sub(self.size());
+        if evicted > 0 {
+            debug!("{} conexão(ões) ociosa(s) com o Kernel em {} encerrada(s).", evicted, self.address);
+        }
+    }
+
+    // Conexão menos ocupada que ainda tem espaço para multiplexar
+    fn least_loaded(&self, with_capacity: bool) -> Option<Arc<KernelConnection>> {
+        self.connections.lock().unwrap().iter()
+            .filter(|conn| !conn.is_closed())
+            .filter(|conn| !with_capacity || conn.in_flight.load(Ordering::SeqCst) < self.config.max_in_flight_per_connection)
+            .min_by_key(|conn| conn.in_flight.load(Ordering::SeqCst))
+            .cloned()
+    }
+
+    // Obtém uma conexão validada com o Kernel deste pool, em até `connect_timeout_ms` no total
+    pub async fn checkout(&self) -> io::Result<Arc<KernelConnection>> {
+        let connect_timeout = Duration::from_millis(self.config.connect_timeout_ms);
+        tokio::time::timeout(connect_timeout, self.checkout_within(connect_timeout)).await
+            .map_err(|_| timed_out("checkout de conexão com o Kernel"))?
+    }
+
+    async fn checkout_within(&self, connect_timeout: Duration) -> io::Result<Arc<KernelConnection>> {
+        let validate_after = Duration::from_secs(self.config.validate_after_idle_secs);
+
+        loop {
+            self.prune();
+
+            let conn = match self.least_loaded(true) {
+                Some(conn) => conn,
+                None => match self.slots.clone().try_acquire_owned() {
+                    // Recém-aberta: não passa pela validação
+                    Ok(permit) => return self.open(connect_timeout, permit).await,
+                    // Pool cheio: compartilha a conexão menos ocupada
+                    Err(_) => match self.least_loaded(false) {
+                        Some(conn) => conn,
+                        // Todas as vagas estão abrindo conexões
+                        None => {
+                            tokio::time::sleep(CHECKOUT_RETRY_DELAY).await;
+                            continue;
+                        }
+                    },
+                },
+            };
+
+            // Validação no checkout: conexões paradas há algum tempo precisam responder ao PING
+            if conn.idle_for() >= validate_after && !conn.ping(connect_timeout).await {
+                conn.closed.store(true, Ordering::SeqCst);
+                warn!(backend = %self.address, "Conexão com o Kernel falhou na validação. Descartada.");
+                continue;
+            }
+            return Ok(conn);
+        }
+    }
+
+    async fn open(&self, timeout: Duration, permit: OwnedSemaphorePermit) -> io::Result<Arc<KernelConnection>> {
+        let conn = Arc::new(KernelConnection::connect(&self.address, timeout, permit).await?);
+        self.connections.lock().unwrap().push(conn.clone());
+        info!("Nova conexão persistente com o Kernel em {} ({}/{}).", self.address, self.size(), self.config.max_size);
+        Ok(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    // Kernel falso: responde PING e ecoa SETTLE, com atraso opcional para exercitar a multiplexação
+    async fn fake_kernel(delay: Duration) -> (String, JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (reader, writer) = stream.into_split();
+                    let writer = Arc::new(tokio::sync::Mutex::new(writer));
+                    let mut lines = BufReader::new(reader).lines();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let writer = writer.clone();
+                        tokio::spawn(async move {
+                            let (id, command) = line.split_once(' ').unwrap();
+                            let body = if command == "PING" { "PONG".to_string() } else { format!("OK {}", command) };
+                            tokio::time::sleep(delay).await;
+                            let _ = writer.lock().await.write_all(format!("{} {}\n", id, body).as_bytes()).await;
+                        });
+                    }
+                });
+            }
+        });
+        (address, task)
+    }
+
+    fn config(max_size: usize) -> PoolConfig {
+        PoolConfig { max_size, validate_after_idle_secs: 0, ..PoolConfig::default() }
+    }
+
+    // Teste 1: Requisições simultâneas compartilham a mesma conexão (pool de tamanho 1).
+    #[tokio::test]
+    async fn test_multiplexed_requests_share_connection() {
+        let (address, _kernel) = fake_kernel(Duration::from_millis(50)).await;
+        let pool = Arc::new(KernelPool::new(&address, config(1)));
+
+        let mut handles = Vec::new();
+        for i in 0..8 {
+            let pool = pool.clone();
+            handles.push(tokio::spawn(async move {
+                let conn = pool.checkout().await.unwrap();
+                conn.request(&format!("SETTLE tx{}", i), pool.request_timeout()).await.unwrap()
+            }));
+        }
+        for (i, handle) in handles.into_iter().enumerate() {
+            assert_eq!(handle.await.unwrap(), format!("OK SETTLE tx{}", i), "Cada resposta deve voltar para sua requisição.");
+        }
+        assert_eq!(pool.size(), 1, "O pool não pode passar do tamanho máximo.");
+    }
+
+    // Teste 2: Conexões mortas são descartadas no checkout e substituídas.
+    #[tokio::test]
+    async fn test_dead_connection_replaced_on_checkout() {
+        let (address, kernel) = fake_kernel(Duration::ZERO).await;
+        let pool = KernelPool::new(&address, config(2));
+
+        let first = pool.checkout().await.unwrap();
+        first.closed.store(true, Ordering::SeqCst);
+        drop(first);
+
+        let second = pool.checkout().await.unwrap();
+        assert!(!second.is_closed());
+        assert_eq!(pool.size(), 1);
+        kernel.abort();
+    }
+
+    // Teste 3: Kernel fora do ar resulta em erro no checkout (base do 503).
+    #[tokio::test]
+    async fn test_checkout_fails_when_kernel_offline() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        let pool = KernelPool::new(&address, config(2));
+        assert!(pool.checkout().await.is_err());
+        assert_eq!(pool.size(), 0);
+    }
+
+    // Teste 4: Conexões ociosas além do limite são removidas.
+    #[tokio::test]
+    async fn test_idle_eviction() {
+        let (address, _kernel) = fake_kernel(Duration::ZERO).await;
+        let pool = KernelPool::new(&address, PoolConfig { idle_timeout_secs: 0, ..config(2) });
+
+        drop(pool.checkout().await.unwrap());
+        pool.evict_idle();
+        assert_eq!(pool.size(), 0);
+    }
+
+    // Teste 5: Envio interrompido no meio da linha conta como enviado e inutiliza a conexão.
+    #[tokio::test]
+    async fn test_partial_write_is_lost() {
+        // Kernel que nunca lê, atrás de um buffer de 64 bytes: a escrita para no meio da linha
+        let (stream, _kernel) = tokio::io::duplex(64);
+        let permit = Arc::new(Semaphore::new(1)).try_acquire_owned().unwrap();
+        let conn = KernelConnection::from_stream(Box::new(stream), permit);
+
+        let payload = format!("SETTLE {}", "A".repeat(1024));
+        let error = conn.request(&payload, Duration::from_millis(200)).await.unwrap_err();
+        assert!(error.was_sent(), "Parte da linha foi escrita: não pode haver failover. {}", error);
+        assert!(conn.is_closed());
+        assert!(!conn.request("PING", Duration::from_millis(200)).await.unwrap_err().was_sent());
+    }
+
+    // Teste 6: Requisição descartada no meio (cliente desconectado) não vaza o registro na conexão.
+    #[tokio::test]
+    async fn test_cancelled_request_is_released() {
+        let (address, _kernel) = fake_kernel(Duration::from_millis(200)).await;
+        let pool = KernelPool::new(&address, config(1));
+        let conn = pool.checkout().await.unwrap();
+
+        let cancelled = tokio::time::timeout(Duration::from_millis(50), conn.request("SETTLE tx1", pool.request_timeout())).await;
+        assert!(cancelled.is_err(), "O future deve ser descartado antes da resposta.");
+        assert_eq!(conn.in_flight.load(Ordering::SeqCst), 0);
+        assert!(conn.pending.lock().unwrap().is_empty());
+        assert!(!conn.is_closed(), "A linha foi escrita inteira: a conexão continua utilizável.");
+        assert_eq!(conn.request("SETTLE tx2", pool.request_timeout()).await.unwrap(), "OK SETTLE tx2");
+    }
+
+    // Teste 7: Kernel que aceita e nunca responde: o checkout não valida a conexão recém-aberta,
+    // termina dentro do prazo, e um timeout de resposta não fecha a conexão multiplexada.
+    #[tokio::test]
+    async fn test_checkout_bounded_with_silent_kernel() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let _kernel = tokio::spawn(async move {
+            let mut streams = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                streams.push(stream);
+            }
+        });
+        let pool = KernelPool::new(&address, PoolConfig { connect_timeout_ms: 100, ..config(1) });
+
+        let started = Instant::now();
+        let conn = pool.checkout().await.expect("A conexão nova é entregue sem PING.");
+        let error = conn.request("SETTLE tx1", Duration::from_millis(50)).await.unwrap_err();
+        assert!(error.was_sent());
+        assert!(!conn.is_closed(), "Timeout de resposta não é falha de I/O.");
+        drop(conn);
+
+        // Reutilizada: o PING falha e a vaga (pool de tamanho 1) não libera a tempo
+        assert!(pool.checkout().await.is_err());
+        assert!(started.elapsed() < Duration::from_secs(1), "O checkout tem prazo total.");
+    }
+}
@@ -0,0 +1,128 @@
+// sygma_proxy/src/lockout.rs - Bloqueio Temporário contra Força Bruta (por IP de origem)
+
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use moka::sync::Cache;
+use serde::Deserialize;
+use tracing::warn;
+
+// Limite de IPs rastreados simultaneamente (a memória não cresce sem limite)
+const MAX_TRACKED_SOURCES: u64 = 10_000;
+
+// --- SEÇÃO `lockout` DO config.yaml ---
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct LockoutConfig {
+    // Por quanto tempo um token rejeitado fica no cache negativo
+    pub negative_cache_ttl_secs: u64,
+    // Falhas permitidas por IP dentro da janela antes do bloqueio
+    pub max_failures: u32,
+    pub window_secs: u64,
+    // Duração do bloqueio temporário
+    pub lockout_secs: u64,
+}
+
+impl Default for LockoutConfig {
+    fn default() -> Self {
+        LockoutConfig {
+            negative_cache_ttl_secs: 30,
+            max_failures: 5,
+            window_secs: 60,
+            lockout_secs: 300,
+        }
+    }
+}
+
+pub struct LockoutTracker {
+    max_failures: u32,
+    lockout_duration: Duration,
+    // Contador de falhas por IP; a entrada expira ao fim da janela
+    failures: Cache<IpAddr, Arc<AtomicU32>>,
+    // IPs bloqueados e o instante em que o bloqueio termina
+    locked: Cache<IpAddr, Instant>,
+}
+
+impl LockoutTracker {
+    pub fn new(config: &LockoutConfig) -> Self {
+        let lockout_duration = Duration::from_secs(config.lockout_secs);
+        LockoutTracker {
+            max_failures: config.max_failures.max(1),
+            lockout_duration,
+            failures: Cache::builder()
+                .max_capacity(MAX_TRACKED_SOURCES)
+                .time_to_live(Duration::from_secs(config.window_secs))
+                .build(),
+            locked: Cache::builder()
+                .max_capacity(MAX_TRACKED_SOURCES)
+                .time_to_live(lockout_duration)
+                .build(),
+        }
+    }
+
+    // Tempo restante de bloqueio, se o IP estiver bloqueado
+    pub fn remaining_lockout(&self, ip: IpAddr) -> Option<Duration> {
+        let until = self.locked.get(&ip)?;
+        let remaining = until.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            self.locked.invalidate(&ip);
+            return None;
+        }
+        Some(remaining)
+    }
+
+    // Registra uma falha de Zero-Trust. Retorna `true` se o IP acabou de ser bloqueado.
+    pub fn record_failure(&self, ip: IpAddr) -> bool {
+        let counter = self.failures.get_with(ip, || Arc::new(AtomicU32::new(0)));
+        let count = counter.fetch_add(1, Ordering::SeqCst) + 1;
+        if count < self.max_failures {
+            return false;
+        }
+
+        self.failures.invalidate(&ip);
+        self.locked.insert(ip, Instant::now() + self.lockout_duration);
+        warn!(
+            "BLOQUEIO: IP {} excedeu {} falhas de Zero-Trust. Bloqueado por {}s.",
+            ip, self.max_failures, self.lockout_duration.as_secs()
+        );
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(max_failures: u32) -> LockoutTracker {
+        LockoutTracker::new(&LockoutConfig { max_failures, ..LockoutConfig::default() })
+    }
+
+    // Teste 1: O IP só é bloqueado ao atingir o limite de falhas.
+    #[test]
+    fn test_lockout_after_threshold() {
+        let tracker = tracker(3);
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+
+        assert!(!tracker.record_failure(ip));
+        assert!(!tracker.record_failure(ip));
+        assert!(tracker.remaining_lockout(ip).is_none());
+
+        assert!(tracker.record_failure(ip), "A terceira falha deve bloquear o IP.");
+        let remaining = tracker.remaining_lockout(ip).expect("IP deve estar bloqueado");
+        assert!(remaining <= Duration::from_secs(300));
+    }
+
+    // Teste 2: O bloqueio de um IP não afeta os demais.
+    #[test]
+    fn test_lockout_is_per_source() {
+        let tracker = tracker(1);
+        let attacker: IpAddr = "10.0.0.2".parse().unwrap();
+        let honest: IpAddr = "10.0.0.3".parse().unwrap();
+
+        assert!(tracker.record_failure(attacker));
+        assert!(tracker.remaining_lockout(attacker).is_some());
+        assert!(tracker.remaining_lockout(honest).is_none());
+    }
+}
@@ -0,0 +1,108 @@
+// sygma_proxy/src/logging.rs - Logs Estruturados (texto ou JSON) e ID de Correlação por Requisição
+//
+// Cada requisição roda num span `request{cid=...}`: todo evento emitido dentro dele leva o cid.
+// O cid segue para o Kernel na linha do protocolo (`<id> cid=<cid> SETTLE ...`) e volta ao
+// cliente na resposta (sufixo `[cid=...]` no TCP, cabeçalho X-Correlation-Id no HTTP).
+// Tokens nunca são logados em claro: use `token::Redacted`.
+
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, EnvFilter, Registry};
+
+// --- SEÇÃO `logging` DO config.yaml ---
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    // Filtro no formato do RUST_LOG: "info" ou "warn,sygma_proxy::kernel_pool=debug" (hot reload)
+    pub level: String,
+    // text | json (exige reinício)
+    pub format: LogFormat,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig { level: "info".to_string(), format: LogFormat::Text }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    Text,
+    // Um objeto JSON por linha, com os campos do span (cid) no topo
+    Json,
+}
+
+// Troca do filtro em tempo de execução (hot reload do config.yaml)
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+pub fn parse_level(level: &str) -> Result<EnvFilter, String> {
+    EnvFilter::try_new(level).map_err(|e| format!("logging.level inválido '{}': {}", level, e))
+}
+
+// Instala o subscriber global. Chamado uma vez, na inicialização.
+pub fn init(config: &LoggingConfig) -> Result<(), String> {
+    let (filter, handle) = reload::Layer::new(parse_level(&config.level)?);
+    let registry = tracing_subscriber::registry().with(filter);
+    let installed = match config.format {
+        LogFormat::Text => registry.with(fmt::layer().with_ansi(io::stdout().is_terminal())).try_init(),
+        LogFormat::Json => registry
+            .with(fmt::layer().json().flatten_event(true).with_current_span(true).with_span_list(false))
+            .try_init(),
+    };
+    installed.map_err(|e| format!("Falha ao iniciar os logs: {}", e))?;
+    let _ = FILTER.set(handle);
+    Ok(())
+}
+
+pub fn set_level(level: &str) -> Result<(), String> {
+    let filter = parse_level(level)?;
+    match FILTER.get() {
+        Some(handle) => handle.reload(filter).map_err(|e| e.to_string()),
+        None => Ok(()),
+    }
+}
+
+// Filtro ativo (após o último reload ou `log-level` do socket de administração)
+pub fn current_level() -> Option<String> {
+    FILTER.get()?.with_current(|filter| filter.to_string()).ok()
+}
+
+static NEXT_CORRELATION: AtomicU64 = AtomicU64::new(0);
+
+// Milissegundos + pid + contador: único entre requisições e entre instâncias do Proxy
+pub fn new_correlation_id() -> String {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    let sequence = NEXT_CORRELATION.fetch_add(1, Ordering::Relaxed) & 0xffff;
+    format!("{:x}{:04x}{:04x}", millis, std::process::id() & 0xffff, sequence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Teste 1: Seção `logging` com padrões, formato JSON e filtro inválido rejeitado.
+    #[test]
+    fn test_logging_config() {
+        assert_eq!(LoggingConfig::default().format, LogFormat::Text);
+        let config: LoggingConfig = serde_yaml::from_str("level: \"warn,sygma_proxy::kernel_pool=debug\"\nformat: json\n").unwrap();
+        assert_eq!(config.format, LogFormat::Json);
+        assert!(parse_level(&config.level).is_ok());
+        assert!(parse_level("sygma_proxy=barulhento").is_err());
+    }
+
+    // Teste 2: IDs de correlação são distintos e seguros para a linha do protocolo.
+    #[test]
+    fn test_correlation_ids() {
+        let first = new_correlation_id();
+        let second = new_correlation_id();
+        assert_ne!(first, second);
+        assert!(first.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+}
@@ -1,135 +1,949 @@
 // sygna_proxy/src/main.rs - Versão com Configuração Externalizada (YAML) e Testes
 
-use tokio::net::{TcpListener, TcpStream};
-use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::io::{self, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
 use moka::sync::Cache;
-use std::time::Duration;
-use serde::Deserialize;
+use std::time::{Duration, Instant};
+use std::path::{Path, PathBuf};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use tracing::{debug, error, info, info_span, warn, Instrument};
 
 #[macro_use]
 extern crate lazy_static;
 
-// --- ESTRUTURA DE DADOS DA CONFIGURAÇÃO YAML ---
-#[derive(Debug, Deserialize)]
-struct Config {
-    proxy_address: String,
-    kernel_address: String,
+mod admin;
+mod audit;
+mod balancer;
+mod cache;
+mod config;
+mod connections;
+mod health;
+mod http_api;
+mod idempotency;
+mod kernel_pool;
+mod lockout;
+mod logging;
+mod metrics;
+mod payload;
+mod policy;
+mod ratelimit;
+mod replay;
+mod revocation;
+mod shutdown;
+mod store_forward;
+mod tls;
+mod token;
+mod unix_socket;
+mod watch;
+
+use cache::{Clock, SystemClock};
+use config::Config;
+use connections::TimedStream;
+use balancer::Balancer;
+use idempotency::{Begin, IdempotencyStore};
+use kernel_pool::{KernelConnection, RequestError};
+use lockout::LockoutTracker;
+use payload::SettlementPayload;
+use policy::{PolicyEngine, Reservation};
+use ratelimit::{RateKey, RateLimiter};
+use replay::ReplayGuard;
+use store_forward::{EnqueueError, StoreForward, TicketStatus};
+use sygma_protocol::SygmaError;
+use tls::{ClientIdentity, TokenMode};
+use token::{Redacted, RedactedSigned, TokenClaims};
+use unix_socket::{ClientListener, Connection, PeerCredentials};
+
+// Intervalo de verificação de mudanças nos arquivos vigiados (config.yaml e revogação)
+const FILE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// Tamanho máximo de uma requisição do protocolo TCP (sem o `\n` final)
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+// O CACHE GLOBAL: Implementação TinyLFU, com a política definida na seção `cache` do YAML
+lazy_static! {
+    static ref CLOCK: Arc<dyn Clock> = Arc::new(SystemClock);
+    static ref TRUST_CACHE: Cache<String, TokenClaims> = cache::build_trust_cache(&config::current().cache, CLOCK.clone());
 }
 
-// O CACHE GLOBAL: Implementação TinyLFU
+// CACHE NEGATIVO: tokens rejeitados recentemente (TTL curto) e bloqueio por IP
 lazy_static! {
-    static ref TRUST_CACHE: Cache<String, bool> = Cache::builder()
-        .max_capacity(10_000) 
-        .time_to_live(Duration::from_secs(300))
+    static ref REJECTED_CACHE: Cache<String, ()> = Cache::builder()
+        .max_capacity(10_000)
+        .time_to_live(Duration::from_secs(config::current().lockout.negative_cache_ttl_secs))
         .build();
+    static ref LOCKOUT: LockoutTracker = LockoutTracker::new(&config::current().lockout);
+    static ref RATE_LIMITER: RateLimiter = RateLimiter::new(&config::current().rate_limit);
 }
 
-// Variável global para armazenar a configuração
+// NONCES VISTOS RECENTEMENTE: proteção contra replay de requisições assinadas
 lazy_static! {
-    static ref APP_CONFIG: Config = load_config().expect("Falha ao carregar config.yaml. O arquivo existe?");
+    static ref REPLAY: ReplayGuard = ReplayGuard::new(&config::current().replay);
 }
 
+// RESPOSTAS POR CHAVE DE IDEMPOTÊNCIA: retentativas não liquidam duas vezes
+lazy_static! {
+    static ref IDEMPOTENCY: IdempotencyStore = IdempotencyStore::new(&config::current().idempotency);
+}
 
-// --- FUNÇÃO DE LEITURA DA CONFIGURAÇÃO ---
-fn load_config() -> Result<Config, io::Error> {
-    let config_path = "config.yaml";
-    let contents = std::fs::read_to_string(config_path)?;
-    
-    let config: Config = serde_yaml::from_str(&contents)
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Erro de parse YAML: {}", e)))?;
-    
-    Ok(config)
+// TOTAIS DIÁRIOS POR CONTA DE ORIGEM: base do teto diário da seção `policy`
+lazy_static! {
+    static ref POLICY: PolicyEngine = PolicyEngine::default();
+}
+
+// BACKENDS DO KERNEL (T1): pools de conexões persistentes + circuit breakers
+lazy_static! {
+    static ref BALANCER: RwLock<Arc<Balancer>> = RwLock::new(Arc::new(Balancer::new(&config::current(), None)));
+}
+
+// Balanceador que corresponde à configuração; recriado após um hot reload que mude os backends
+fn balancer_for(config: &Config) -> Arc<Balancer> {
+    let current = BALANCER.read().unwrap().clone();
+    if current.matches(config) {
+        return current;
+    }
+    let mut active = BALANCER.write().unwrap();
+    if !active.matches(config) {
+        *active = Arc::new(Balancer::new(config, Some(&active)));
+        info!("Backends do Kernel atualizados ({} backend(s)).", active.backends().len());
+    }
+    active.clone()
 }
 
 
 // --- FUNÇÕES CORE DO PROXY ---
 
-// Verifica se o Kernel (T1) está disponível, usando o endereço LIDO do YAML
-async fn check_kernel_health() -> bool {
-    match TcpStream::connect(APP_CONFIG.kernel_address.as_str()).await {
-        Ok(_) => true,
-        Err(_) => false,
+// Envia o payload ao Kernel e traduz o veredito para o protocolo do cliente.
+// O ID de correlação vai junto, para que os logs do Kernel possam ser cruzados com os do Proxy.
+// `Err` significa falha de transporte (o Kernel caiu no meio do Settlement).
+async fn forward_to_kernel(conn: &KernelConnection, payload: &str, cid: &str, timeout: Duration) -> Result<String, RequestError> {
+    let response = conn.request(&format!("cid={} SETTLE {}", cid, payload), timeout).await?;
+    Ok(match response.split_once(' ') {
+        Some(("OK", proof_hash)) => format!("200 OK: Payload {} liquidado pelo Kernel T1 (Prova {}).", payload, proof_hash),
+        Some(("REJECTED", reason)) => SygmaError::SettlementRejected(reason.to_string()).response(),
+        _ => {
+            error!("Resposta inesperada do Kernel: {}", response);
+            SygmaError::KernelBadResponse.response()
+        }
+    })
+}
+
+// 2 + 3. HEALTH CHECK E ROTEAMENTO COM FAILOVER
+// Backends com o circuito aberto ficam fora da rotação. Uma requisição que nunca chegou
+// ao Kernel é segura para reenviar a outro backend; um SETTLE já entregue não é, pois
+// poderia ser liquidado duas vezes.
+async fn route_to_kernel(config: &Config, payload: &SettlementPayload, entry: &mut audit::Entry) -> String {
+    let balancer = balancer_for(config);
+    // A conta de origem é a chave do hash consistente
+    let sender = payload.sender.to_string();
+    let payload = payload.to_string();
+    let mut excluded = vec![false; balancer.backends().len()];
+
+    loop {
+        let selected = match balancer.select(Some(&sender), &mut excluded) {
+            Ok(selected) => selected,
+            Err(Some(retry_after)) => {
+                warn!("REJEIÇÃO: Circuit breaker aberto em todos os backends. Kernel T1 não foi contatado.");
+                return SygmaError::KernelUnavailable { retry_after_secs: Some(retry_after.as_secs().max(1)) }.response();
+            }
+            Err(None) => {
+                warn!("REJEIÇÃO: Kernel T1 indisponível. Conexão bloqueada para prevenir perda de dados.");
+                return SygmaError::KernelUnavailable { retry_after_secs: None }.response();
+            }
+        };
+        let backend = &selected.backend;
+
+        let conn = match backend.pool.checkout().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!("Kernel em {} indisponível: {}", backend.address, e);
+                backend.breaker.record(selected.admission, false);
+                excluded[selected.index] = true;
+                continue;
+            }
+        };
+
+        info!("Roteando payload para o Kernel em {} (Health Check OK)...", backend.address);
+        let _active = backend.begin();
+        let started = Instant::now();
+        let result = forward_to_kernel(&conn, &payload, &entry.cid, backend.pool.request_timeout()).await;
+        metrics::METRICS.record_kernel_request(&backend.address, started.elapsed());
+        match result {
+            Ok(response) => {
+                backend.breaker.record(selected.admission, true);
+                entry.kernel(&backend.address, started.elapsed());
+                return response;
+            }
+            Err(e) => {
+                backend.breaker.record(selected.admission, false);
+                error!("Kernel em {} falhou durante o Settlement: {}", backend.address, e);
+                if e.was_sent() {
+                    entry.kernel(&backend.address, started.elapsed());
+                    return SygmaError::KernelFailed.response();
+                }
+                warn!("FAILOVER: Requisição não chegou a {}. Tentando outro backend.", backend.address);
+                excluded[selected.index] = true;
+            }
+        }
+    }
+}
+
+// Sonda de saúde em segundo plano: PING de aplicação em cada backend, alimentando seu circuit breaker
+async fn monitor_kernel_health() {
+    let health = config::current().health.clone();
+    let probe_timeout = Duration::from_millis(health.probe_timeout_ms);
+    let mut ticker = tokio::time::interval(Duration::from_millis(health.probe_interval_ms));
+
+    loop {
+        ticker.tick().await;
+        let balancer = balancer_for(&config::current());
+        for backend in balancer.backends().iter().cloned() {
+            tokio::spawn(async move {
+                let started = Instant::now();
+                let healthy = match backend.pool.checkout().await {
+                    Ok(conn) => matches!(conn.request("PING", probe_timeout).await.as_deref(), Ok("PONG")),
+                    Err(_) => false,
+                };
+                metrics::METRICS.record_probe(&backend.address, healthy, started.elapsed());
+                backend.breaker.record_probe(healthy);
+            });
+        }
     }
 }
 
 
 // 1. VERIFICAR AUTENTICAÇÃO (TORNADA PÚBLICA PARA O TESTE)
 pub async fn verify_zero_trust_token(token: &str) -> bool {
-    if let Some(is_valid) = TRUST_CACHE.get(token) {
-        println!("[PROXY-CACHE]: Token '{}' encontrado no TinyLFU. Verificação ignorada (RÁPIDO).", token);
-        return is_valid;
+    authenticate_token(token).await.is_some()
+}
+
+// Zero-Trust Check completo; devolve os claims do token aceito (subject e classe para o rate limit)
+async fn authenticate_token(token: &str) -> Option<TokenClaims> {
+    // nonce, ts e sig mudam a cada requisição: os caches usam o token sem eles
+    let key = token::cache_key(token);
+
+    // A revogação tem prioridade sobre o cache e sobre o formato do token
+    if revocation::is_revoked(token) || revocation::is_revoked(&key) {
+        TRUST_CACHE.invalidate(&key);
+        warn!("{} está revogado. Acesso negado.", Redacted(token));
+        return None;
     }
 
-    // A lógica de validação é que o token COMECE com AUTH_SYGMA_VALID_
-    let is_valid = token.starts_with("AUTH_SYGMA_VALID_"); 
+    // Com chave HMAC configurada, toda requisição precisa de assinatura válida. Uma assinatura
+    // inválida não entra no cache negativo: bloquearia o token legítimo com a mesma chave.
+    let config = config::current();
+    let signed = match config.auth.hmac_key.as_deref() {
+        Some(hmac_key) if !token::verify_signature(token, hmac_key.as_bytes()) => {
+            debug!("{} com assinatura inválida.", Redacted(token));
+            return None;
+        }
+        Some(_) => true,
+        None => false,
+    };
+    let claims = verify_token_key(&key)?;
 
-    if is_valid {
-        TRUST_CACHE.insert(token.to_string(), true);
-        println!("[PROXY-CACHE]: Token '{}' verificado e adicionado ao TinyLFU.", token);
+    // nonce e ts só valem assinados; sem assinatura qualquer um pode trocá-los a cada reenvio
+    if !signed {
+        return Some(claims);
     }
-    
-    is_valid
+    let request = TokenClaims::parse(token)?;
+    Some(TokenClaims { nonce: request.nonce, ts: request.ts, ..claims })
 }
 
-// 2. ROTEAMENTO SEGURO DE CONEXÕES 
-async fn handle_connection(mut stream: TcpStream) -> io::Result<()> {
-    let mut buffer = [0; 1024];
-    let n = stream.read(&mut buffer).await?;
-    let request_data = String::from_utf8_lossy(&buffer[..n]);
-    let parts: Vec<&str> = request_data.split('|').collect();
-    
-    if parts.len() < 2 {
-        stream.write_all(b"400 ERROR: Invalid Sygma Request Format").await?;
-        return Ok(());
+// Verificação do token (sem nonce, ts e sig), com o TRUST_CACHE e o cache negativo
+fn verify_token_key(key: &str) -> Option<TokenClaims> {
+    let cached = cache::cached_claims(&TRUST_CACHE, key, CLOCK.now_unix());
+    metrics::METRICS.record_trust_cache(cached.is_some());
+    if let Some(claims) = cached {
+        debug!("{} encontrado no TinyLFU. Verificação ignorada (RÁPIDO).", Redacted(key));
+        return Some(claims);
+    }
+
+    if REJECTED_CACHE.contains_key(key) {
+        debug!("{} rejeitado recentemente (cache negativo). Verificação ignorada.", Redacted(key));
+        return None;
     }
 
-    let auth_token = parts[0].trim();
-    let kernel_payload = parts[1].trim();
+    // A lógica de validação é que o token COMECE com AUTH_SYGMA_VALID_, com claims bem formados e não expirados
+    let claims = TokenClaims::parse(key).filter(|claims| !claims.is_expired(CLOCK.now_unix()));
+
+    match claims {
+        Some(claims) => {
+            TRUST_CACHE.insert(key.to_string(), claims.clone());
+            debug!("{} verificado e adicionado ao TinyLFU.", Redacted(key));
+            Some(claims)
+        }
+        None => {
+            REJECTED_CACHE.insert(key.to_string(), ());
+            None
+        }
+    }
+}
+
+// Resultado do pipeline de Settlement: a resposta ao cliente e, se autenticado, o subject.
+// `replayed`: resposta original de uma chave de idempotência já vista (o Kernel não foi contatado).
+pub struct Outcome {
+    pub response: String,
+    pub subject: Option<String>,
+    pub replayed: bool,
+}
+
+impl Outcome {
+    fn rejected(error: SygmaError) -> Self {
+        Outcome { response: error.response(), subject: None, replayed: false }
+    }
+}
+
+// 0. Verificações da origem (bloqueio por força bruta, credenciais do cliente unix e certificado
+// de cliente), comuns a todas as rotas e listeners. `Ok` traz a identidade do certificado (mTLS),
+// `Err` a rejeição.
+fn check_source(config: &Config, addr: SocketAddr, peer: Option<PeerCredentials>, client_identity: ClientIdentity) -> Result<Option<String>, SygmaError> {
+    // IP bloqueado nem chega ao Zero-Trust Check
+    if let Some(remaining) = LOCKOUT.remaining_lockout(addr.ip()) {
+        warn!("REJEIÇÃO: IP {} bloqueado por falhas repetidas de Zero-Trust.", addr.ip());
+        return Err(SygmaError::LockedOut { retry_after_secs: remaining.as_secs().max(1) });
+    }
+
+    // Socket unix: o uid do processo cliente, informado pelo SO, precisa ser aceito
+    if let Some(peer) = peer.filter(|peer| !peer.allowed(&config.unix_socket)) {
+        warn!("REJEIÇÃO: Cliente {} não autorizado no socket unix.", peer);
+        LOCKOUT.record_failure(addr.ip());
+        return Err(SygmaError::PeerNotAllowed);
+    }
+
+    // mTLS: sem certificado de cliente válido, nada mais é avaliado
+    match client_identity {
+        Some(Ok(identity)) => Ok(Some(identity)),
+        Some(Err(reason)) => {
+            warn!("REJEIÇÃO: Certificado de cliente de {} recusado: {}.", addr, reason);
+            LOCKOUT.record_failure(addr.ip());
+            Err(SygmaError::CertificateRejected(reason.to_string()))
+        }
+        None => Ok(None),
+    }
+}
+
+// Estado do circuit breaker de cada backend do Kernel
+fn backend_states(config: &Config) -> Vec<(String, health::BreakerState)> {
+    balancer_for(config).backends().iter()
+        .map(|backend| (backend.address.clone(), backend.breaker.state()))
+        .collect()
+}
+
+// 1. ZERO-TRUST CHECK
+// Com `token_mode: instead`, a identidade do certificado substitui o token
+async fn authenticate(config: &Config, cert_identity: Option<String>, auth_token: &str) -> Option<TokenClaims> {
+    match cert_identity {
+        Some(identity) if certificate_replaces_token(config) => {
+            info!("Identidade '{}' autenticada pelo certificado de cliente (mTLS).", identity);
+            Some(TokenClaims { subject: identity, exp: None, tier: None, scopes: Vec::new(), nonce: None, ts: None })
+        }
+        _ => authenticate_token(auth_token).await,
+    }
+}
+
+fn certificate_replaces_token(config: &Config) -> bool {
+    let token_mode = config.tls.as_ref().and_then(|tls| tls.client_auth.as_ref()).map(|client_auth| client_auth.token_mode);
+    token_mode == Some(TokenMode::Instead)
+}
+
+// Pipeline de Settlement comum aos listeners TCP e HTTP:
+// rate limit por IP -> payload -> Zero-Trust -> idempotência -> replay -> rate limit por token ->
+// política -> health check e roteamento (ou fila store-and-forward)
+async fn settle(config: &Config, addr: SocketAddr, cert_identity: Option<String>, auth_token: &str, kernel_payload: &str, idempotency_key: Option<&str>, entry: &mut audit::Entry) -> Outcome {
+    entry.token(auth_token);
+    entry.payload(kernel_payload);
+
+    // 0b. RATE LIMIT POR IP: protege o Zero-Trust Check contra inundação
+    if config.rate_limit.enabled {
+        if let Err(wait) = RATE_LIMITER.check(RateKey::Ip(addr.ip()), &config.rate_limit.per_ip) {
+            warn!("REJEIÇÃO: IP {} excedeu o rate limit.", addr.ip());
+            return Outcome::rejected(rate_limited(wait));
+        }
+    }
+
+    // Payload tipado: só payloads válidos chegam ao Kernel, sempre na forma canônica
+    let payload = match kernel_payload.parse::<SettlementPayload>() {
+        Ok(payload) => payload,
+        Err(e) => {
+            warn!("REJEIÇÃO: Payload inválido de {}: {}.", addr, e);
+            return Outcome::rejected(e.error());
+        }
+    };
+    entry.payload(&payload.to_string());
+    if idempotency_key.is_some_and(|key| !idempotency::valid_key(key)) {
+        return Outcome::rejected(SygmaError::InvalidIdempotencyKey);
+    }
 
     // 1. ZERO-TRUST CHECK
-    if !verify_zero_trust_token(auth_token).await {
-        stream.write_all(b"403 ACCESS DENIED: Zero Trust Violation").await?;
-        println!("PROXY: REJEIÇÃO: Token {} falhou no Zero-Trust Check.", auth_token);
-        return Ok(());
+    let by_certificate = cert_identity.is_some() && certificate_replaces_token(config);
+    let Some(claims) = authenticate(config, cert_identity, auth_token).await else {
+        warn!("REJEIÇÃO: {} falhou no Zero-Trust Check.", Redacted(auth_token));
+        LOCKOUT.record_failure(addr.ip());
+        return Outcome::rejected(SygmaError::ZeroTrustViolation);
+    };
+
+    // 1a. IDEMPOTÊNCIA: a repetição de uma chave já vista recebe a resposta original. Vem antes
+    // do replay: a retentativa de rede reenvia o mesmo token, com o nonce já consumido.
+    let pending = match idempotency_key.map(|key| IDEMPOTENCY.begin(&claims.subject, key, &payload.to_string())) {
+        None => None,
+        Some(Begin::New(pending)) => Some(pending),
+        Some(Begin::Replay(response)) => {
+            info!("Chave de idempotência repetida por '{}'. Devolvendo a resposta original.", claims.subject);
+            entry.replayed();
+            return Outcome { response, subject: Some(claims.subject), replayed: true };
+        }
+        Some(Begin::InProgress) => return Outcome::rejected(SygmaError::IdempotencyInProgress),
+        Some(Begin::Mismatch) => {
+            warn!("REJEIÇÃO: Chave de idempotência de '{}' reutilizada com outro payload.", claims.subject);
+            return Outcome::rejected(SygmaError::IdempotencyKeyReused);
+        }
+    };
+
+    // 1b. REPLAY: nonce e timestamp do token (vale também para tokens já no TRUST_CACHE).
+    // Com a identidade do certificado no lugar do token, o próprio TLS impede o replay.
+    if !by_certificate {
+        if let Err(rejection) = REPLAY.check(&claims, CLOCK.now_unix()) {
+            // Com `auth.hmac_key`, a assinatura do token já foi verificada pelo Zero-Trust Check
+            let token = match config.auth.hmac_key {
+                Some(_) => RedactedSigned { token: auth_token, subject: &claims.subject }.to_string(),
+                None => Redacted(auth_token).to_string(),
+            };
+            warn!("REJEIÇÃO: {} recusado pela proteção contra replay ({:?}).", token, rejection);
+            return Outcome::rejected(rejection.error());
+        }
     }
 
-    // 2. HEALTH CHECK
-    if !check_kernel_health().await {
-        stream.write_all(b"503 SERVICE UNAVAILABLE: Kernel T1 Offline").await?;
-        println!("PROXY: REJEIÇÃO: Kernel T1 indisponível. Conexão bloqueada para prevenir perda de dados.");
-        return Ok(());
+    // 1c. RATE LIMIT POR TOKEN: faixa definida pela classe do token (claim `tier`)
+    if config.rate_limit.enabled {
+        let limit = config.rate_limit.for_tier(claims.tier.as_deref());
+        if let Err(wait) = RATE_LIMITER.check(RateKey::Subject(claims.subject.clone()), limit) {
+            warn!("REJEIÇÃO: Token de '{}' excedeu o rate limit.", claims.subject);
+            return Outcome::rejected(rate_limited(wait));
+        }
+    }
+
+    // 1d. POLÍTICA: tetos por transação e diário, listas de destinatários e limites por escopo
+    let mut reservation = match POLICY.check(&config.policy, &claims, &payload, CLOCK.now_unix()) {
+        Ok(reservation) => Some(reservation),
+        Err(denial) => {
+            let error = denial.error();
+            warn!("REJEIÇÃO: Transação de '{}' negada pela política: {}", claims.subject, error);
+            return Outcome::rejected(error);
+        }
+    };
+
+    // 2. HEALTH CHECK + 3. ROTEAMENTO SEGURO
+    // Com Settlements já na fila store-and-forward, o novo entra no fim dela para manter a ordem
+    let queue = store_forward::queue();
+    let response = match queue.filter(|queue| queue.pending() > 0) {
+        Some(queue) => enqueue_settlement(queue, &claims.subject, &payload, &entry.cid, &mut reservation)
+            .unwrap_or_else(|| SygmaError::QueueFull.response()),
+        None => {
+            let response = route_to_kernel(config, &payload, entry).await;
+            match queue {
+                Some(queue) if metrics::status_code(&response) == 503 => {
+                    enqueue_settlement(queue, &claims.subject, &payload, &entry.cid, &mut reservation).unwrap_or(response)
+                }
+                _ => response,
+            }
+        }
+    };
+    // Conta no total diário o que foi (ou pode ter sido, no 502) liquidado; o que foi para a fila
+    // fica reservado até a entrega
+    if let Some(reservation) = reservation.filter(|_| matches!(metrics::status_code(&response), 200 | 502)) {
+        reservation.commit();
+    }
+    if let Some(pending) = pending {
+        pending.complete(&response);
+    }
+    Outcome { response, subject: Some(claims.subject), replayed: false }
+}
+
+// 2a. STORE-AND-FORWARD: o Settlement fica na fila durável e o cliente recebe um ticket; a
+// reserva da política fica com o ticket. `None` (fila cheia ou falha de disco) mantém o 503.
+fn enqueue_settlement(queue: &StoreForward, subject: &str, payload: &SettlementPayload, cid: &str, reservation: &mut Option<Reservation>) -> Option<String> {
+    let reserved = reservation.as_ref().map_or(0, Reservation::amount);
+    match queue.enqueue(subject, &payload.to_string(), cid, reserved) {
+        Ok(ticket) => {
+            info!("Settlement de '{}' na fila store-and-forward (Ticket {}).", subject, ticket);
+            if let Some(reservation) = reservation.take() {
+                POLICY.hold(&ticket, reservation);
+            }
+            Some(format!("202 ACCEPTED: Kernel T1 Offline, queued for delivery (Ticket: {})", ticket))
+        }
+        Err(EnqueueError::Full) => {
+            warn!("REJEIÇÃO: Fila store-and-forward cheia. Settlement de '{}' não foi aceito.", subject);
+            None
+        }
+        Err(EnqueueError::Io(e)) => {
+            error!("Falha ao gravar a fila store-and-forward: {}", e);
+            None
+        }
+    }
+}
+
+// Entrega da fila store-and-forward, em ordem. Um 503 interrompe a passada e o Settlement continua
+// no início da fila; qualquer outra resposta (inclusive o 502 incerto, nunca reenviado) é final.
+// Cada entrega conta na drenagem do encerramento; depois do sinal, nenhuma nova começa.
+async fn deliver_queued_settlements(queue: &'static StoreForward) {
+    loop {
+        while let Some(queued) = queue.head() {
+            // Registrada antes de olhar o sinal: ou a drenagem espera esta entrega, ou ela não começa
+            let _in_flight = shutdown::DRAIN.begin();
+            if shutdown::DRAIN.is_stopping() {
+                return;
+            }
+            let started = Instant::now();
+            let config = config::current();
+            let span = info_span!("request", cid = %queued.cid, listener = "queue", peer = %queued.ticket);
+            let mut entry = audit::Entry::new("queue", queued.ticket.clone(), queued.cid.clone());
+            entry.payload(&queued.payload);
+            if let Err(e) = queue.begin_delivery(&queued.ticket) {
+                error!("Falha ao gravar a entrega do Ticket {}: {}", queued.ticket, e);
+                break;
+            }
+            let response = match queued.payload.parse::<SettlementPayload>() {
+                Ok(payload) => route_to_kernel(&config, &payload, &mut entry).instrument(span).await,
+                Err(e) => e.error().response(),
+            };
+            let code = metrics::status_code(&response);
+            if code == 503 {
+                if let Err(e) = queue.requeue(&queued.ticket) {
+                    error!("Falha ao gravar a devolução do Ticket {} à fila: {}", queued.ticket, e);
+                }
+                break;
+            }
+            info!("Ticket {} entregue pela fila store-and-forward: {}", queued.ticket, response);
+            if let Err(e) = queue.finish(&queued.ticket, &response) {
+                error!("Falha ao gravar o resultado do Ticket {}: {}", queued.ticket, e);
+            }
+            POLICY.resolve(&queued.ticket, matches!(code, 200 | 502));
+            metrics::METRICS.record_request("queue", code, started.elapsed());
+            entry.response(&response);
+            entry.finish(code, started.elapsed());
+        }
+        // Com o Kernel fora do ar, espera o intervalo inteiro; com a fila vazia, acorda a cada novo item
+        let wait = async {
+            match queue.pending() {
+                0 => queue.wait().await,
+                _ => tokio::time::sleep(queue.retry_interval()).await,
+            }
+        };
+        tokio::select! {
+            _ = wait => {}
+            _ = shutdown::DRAIN.stopped() => return,
+        }
+    }
+}
+
+// Consulta de ticket (`token|TICKET <ticket>`): só o subject que criou o ticket o enxerga
+async fn ticket_status(config: &Config, addr: SocketAddr, cert_identity: Option<String>, auth_token: &str, ticket: &str) -> String {
+    let Some(claims) = authenticate(config, cert_identity, auth_token).await else {
+        warn!("REJEIÇÃO: {} falhou no Zero-Trust Check.", Redacted(auth_token));
+        LOCKOUT.record_failure(addr.ip());
+        return SygmaError::ZeroTrustViolation.response();
+    };
+    let Some(status) = store_forward::queue().and_then(|queue| queue.status(&claims.subject, ticket)) else {
+        return SygmaError::TicketNotFound.response();
+    };
+    match &status {
+        TicketStatus::Queued(position) => format!("200 OK: Ticket {} queued (position {})", ticket, position),
+        TicketStatus::Finished(response) => format!("200 OK: Ticket {} {} -> {}", ticket, status.label(), response),
+    }
+}
+
+// `token|TICKET <ticket>` -> o ticket
+fn ticket_lookup(request_data: &str) -> Option<(&str, &str)> {
+    let (token, command) = request_data.split_once('|')?;
+    let ticket = command.trim().strip_prefix("TICKET ")?;
+    Some((token.trim(), ticket.trim()))
+}
+
+// Requisição TCP: até o `\n` (ou o fim da conexão), com no máximo MAX_REQUEST_BYTES. O prazo
+// da fase Request (connections.request_timeout_ms) vale para a linha inteira, via TimedStream.
+async fn read_request<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<Result<String, SygmaError>> {
+    let mut buffer = Vec::new();
+    let mut reader = BufReader::new(stream).take(MAX_REQUEST_BYTES as u64 + 1);
+    match reader.read_until(b'\n', &mut buffer).await {
+        Ok(_) => {}
+        // Parte da requisição chegou: o cliente recebe o 408 antes do fechamento
+        Err(e) if e.kind() == io::ErrorKind::TimedOut && !buffer.is_empty() => return Ok(Err(SygmaError::RequestTimeout)),
+        Err(e) => return Err(e),
+    }
+    if buffer.ends_with(b"\n") {
+        buffer.pop();
     }
+    if buffer.len() > MAX_REQUEST_BYTES {
+        return Ok(Err(SygmaError::PayloadTooLarge { limit_bytes: MAX_REQUEST_BYTES }));
+    }
+    Ok(Ok(String::from_utf8_lossy(&buffer).into_owned()))
+}
+
+// 2. ROTEAMENTO SEGURO DE CONEXÕES (protocolo TCP `token|payload[|chave de idempotência]\n`)
+// Clientes de um `proxy_address` unix:/caminho chegam com `peer` (uid e pid) e o listener "unix"
+// Erros terminam com a marca `[err=<código> retryable=<bool>]` de sygma_protocol, antes do `[cid=...]`
+async fn handle_connection<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S, addr: SocketAddr, peer: Option<PeerCredentials>, client_identity: ClientIdentity) -> io::Result<()> {
+    let request = read_request(&mut stream).await?;
+    let started = Instant::now();
+    let cid = logging::new_correlation_id();
+    let (listener, source) = match peer {
+        Some(peer) => ("unix", peer.to_string()),
+        None => ("tcp", addr.to_string()),
+    };
+    let span = info_span!("request", cid = %cid, listener, peer = %source);
+    let mut entry = audit::Entry::new(listener, source, cid.clone());
+
+    let (request_data, response) = match request {
+        Ok(request_data) => {
+            let response = respond(&request_data, addr, peer, client_identity, &mut entry).instrument(span).await;
+            (request_data, response)
+        }
+        Err(error) => {
+            span.in_scope(|| warn!("REJEIÇÃO: Requisição de {} recusada: {}.", addr, error));
+            (String::new(), error.response())
+        }
+    };
+    // O cliente recebe o ID de correlação no fim da resposta
+    stream.write_all(format!("{} [cid={}]", response, cid).as_bytes()).await?;
+    let code = metrics::status_code(&response);
+    metrics::METRICS.record_request(listener, code, started.elapsed());
+    // HEALTH e TICKET não são decisões sobre Settlement; todo o resto vai para a trilha de auditoria
+    if request_data.trim() != "HEALTH" && ticket_lookup(&request_data).is_none() {
+        entry.response(&response);
+        entry.finish(code, started.elapsed());
+    }
+
+    Ok(())
+}
+
+// Resposta a uma requisição do protocolo TCP (`token|payload`, `token|TICKET <ticket>` ou `HEALTH`)
+async fn respond(request_data: &str, addr: SocketAddr, peer: Option<PeerCredentials>, client_identity: ClientIdentity, entry: &mut audit::Entry) -> String {
+    // Snapshot da configuração: uma recarga durante esta requisição não a afeta
+    let config = config::current();
 
-    // 3. ROTEAMENTO SEGURO
-    println!("PROXY: Roteando payload para o Kernel (Health Check OK)...");
+    let cert_identity = match check_source(&config, addr, peer, client_identity) {
+        Ok(identity) => identity,
+        Err(error) => return error.response(),
+    };
+
+    // Consulta de saúde: expõe o estado do circuit breaker do Kernel
+    if request_data.trim() == "HEALTH" {
+        let states: Vec<String> = backend_states(&config).iter()
+            .map(|(address, state)| format!("{}={}", address, state))
+            .collect();
+        return format!("200 OK: Kernel T1 {}", states.join(" "));
+    }
+
+    if let Some((auth_token, ticket)) = ticket_lookup(request_data) {
+        return ticket_status(&config, addr, cert_identity, auth_token, ticket).await;
+    }
+
+    let parts: Vec<&str> = request_data.split('|').collect();
     
-    let kernel_response = format!("200 OK: Payload {} submetido ao Kernel T1. Aguardando Settlement.", kernel_payload);
-    stream.write_all(kernel_response.as_bytes()).await?;
+    if parts.len() < 2 {
+        return SygmaError::InvalidRequest.response();
+    }
+
+    // Terceiro campo opcional: chave de idempotência (`token|payload|chave`)
+    let idempotency_key = parts.get(2).map(|key| key.trim());
+    settle(&config, addr, cert_identity, parts[0].trim(), parts[1].trim(), idempotency_key, entry).await.response
+}
+
+// Distinto do 429 de bloqueio: o cliente só precisa esperar `Retry-After` e tentar de novo
+fn rate_limited(wait: Duration) -> SygmaError {
+    SygmaError::RateLimited { retry_after_secs: wait.as_secs_f64().ceil().max(1.0) as u64 }
+}
 
+// Comando administrativo: `sygma_proxy revoke <token>` acrescenta o token ao
+// arquivo de revogação; o Proxy em execução aplica a mudança ao detectá-la.
+fn run_revoke_command(token: Option<String>) -> io::Result<()> {
+    let token = token.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Uso: sygma_proxy revoke <token>"))?;
+    let config = config::current();
+    let path = config.revocation_file.as_deref()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "revocation_file não definido no config.yaml"))?;
+
+    revocation::append_to_revocation_file(path.as_ref(), &token)?;
+    println!("PROXY-ADMIN: {} adicionado à lista de revogação ({}).", Redacted(&token), path);
     Ok(())
 }
 
+// Comandos do socket de administração (admin.rs), executados sobre o estado do Proxy em execução
+fn run_admin_command(command: admin::Command) -> Result<String, String> {
+    match command {
+        admin::Command::CacheStats => {
+            TRUST_CACHE.run_pending_tasks();
+            let (hits, misses) = metrics::METRICS.trust_cache_lookups();
+            Ok(format!(
+                "entries={} max_capacity={} hits={} misses={}",
+                TRUST_CACHE.entry_count(), config::current().cache.max_capacity, hits, misses,
+            ))
+        }
+        admin::Command::CacheList => {
+            // Sem chave HMAC, o subject basta para montar o token: fica de fora da listagem
+            let signed = config::current().auth.hmac_key.is_some();
+            let mut lines: Vec<String> = TRUST_CACHE.iter()
+                .map(|(token, claims)| {
+                    let optional = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+                    let subject = optional(signed.then_some(claims.subject));
+                    format!("{} subject={} tier={} exp={}", Redacted(&token), subject, optional(claims.tier), optional(claims.exp.map(|exp| exp.to_string())))
+                })
+                .collect();
+            lines.sort();
+            Ok(lines.join("\n"))
+        }
+        admin::Command::CacheInvalidate(target) => {
+            // Aceita o token em claro ou a forma redigida exibida por `cache list`
+            let key = token::cache_key(&target);
+            let matching: Vec<_> = TRUST_CACHE.iter()
+                .map(|(token, _)| token)
+                .filter(|token| **token == key || Redacted(token).to_string() == target)
+                .collect();
+            if matching.is_empty() {
+                return Err(format!("{} is not in TRUST_CACHE", Redacted(&target)));
+            }
+            for token in &matching {
+                TRUST_CACHE.invalidate(token.as_str());
+            }
+            Ok(format!("invalidated={}", matching.len()))
+        }
+        admin::Command::CacheInvalidateAll => {
+            TRUST_CACHE.run_pending_tasks();
+            let entries = TRUST_CACHE.entry_count();
+            TRUST_CACHE.invalidate_all();
+            Ok(format!("invalidated={}", entries))
+        }
+        admin::Command::Backends => Ok(balancer_for(&config::current()).backends().iter()
+            .map(|backend| format!(
+                "{} weight={} breaker={} pool_connections={} active_requests={}",
+                backend.address, backend.weight, backend.breaker.state(), backend.pool.size(), backend.active_requests(),
+            ))
+            .collect::<Vec<_>>()
+            .join("\n")),
+        admin::Command::Reload => config::reload_config(config::config_path())
+            .map(|()| format!("reloaded {}", config::config_path().display()))
+            .map_err(|e| e.to_string()),
+        admin::Command::LogLevel(None) => logging::current_level().ok_or_else(|| "logging not initialized".to_string()),
+        admin::Command::LogLevel(Some(level)) => {
+            logging::set_level(&level)?;
+            info!("Nível de log alterado para '{}' pelo socket de administração.", level);
+            Ok(format!("level={}", level))
+        }
+    }
+}
+
+// --- LINHA DE COMANDO ---
+// sygma_proxy [--config <arquivo>] [--check-config] [revoke <token>]
+struct CliArgs {
+    config_path: PathBuf,
+    check_config: bool,
+    command: Vec<String>,
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> io::Result<CliArgs> {
+    let mut cli = CliArgs { config_path: PathBuf::from(config::DEFAULT_CONFIG_PATH), check_config: false, command: Vec::new() };
+    let mut args = args.peekable();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => {
+                let path = args.next()
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "--config exige um caminho"))?;
+                cli.config_path = PathBuf::from(path);
+            }
+            "--check-config" => cli.check_config = true,
+            _ if arg.starts_with("--config=") => cli.config_path = PathBuf::from(&arg["--config=".len()..]),
+            _ if arg.starts_with("--") => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Opção desconhecida: {}", arg)));
+            }
+            _ => cli.command.push(arg),
+        }
+    }
+    Ok(cli)
+}
+
 // ----------------------------------------------------------------------
 // FUNÇÃO PRINCIPAL: Inicia o Listener Assíncrono
 // ----------------------------------------------------------------------
 #[tokio::main]
 async fn main() -> io::Result<()> {
+    let cli = parse_args(std::env::args().skip(1))?;
+
+    let startup_config = match config::init(cli.config_path.clone()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("PROXY-CONFIG: Configuração inválida em {}: {}", cli.config_path.display(), e);
+            std::process::exit(1);
+        }
+    };
+    if cli.check_config {
+        println!("PROXY-CONFIG: {} OK.", cli.config_path.display());
+        return Ok(());
+    }
+
+    let mut command = cli.command.into_iter();
+    if let Some(name) = command.next() {
+        return match name.as_str() {
+            "revoke" => run_revoke_command(command.next()),
+            other => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Comando desconhecido: {}", other))),
+        };
+    }
+
+    // Logs estruturados (texto ou JSON) a partir daqui
+    logging::init(&startup_config.logging).map_err(io::Error::other)?;
+
     let _ = TRUST_CACHE.entry_count(); 
-    let _ = APP_CONFIG.proxy_address.as_str();
 
-    let listener = TcpListener::bind(APP_CONFIG.proxy_address.as_str()).await?;
-    println!("--- Sygma Proxy (Tier 2 Agent) escutando em {} (YAML Config + Health Check Ativo) ---", APP_CONFIG.proxy_address);
+    // Trilha de auditoria JSONL (thread de escrita própria, fora do caminho das requisições)
+    audit::init(startup_config.audit.as_ref())?;
+
+    // Fila store-and-forward: Settlements pendentes de uma execução anterior são entregues primeiro
+    store_forward::init(startup_config.store_and_forward.as_ref())?;
+    if let Some(queue) = store_forward::queue() {
+        // Os recuperados voltam a contar no teto diário até a entrega
+        for queued in queue.pending_items() {
+            if let Ok(payload) = queued.payload.parse::<SettlementPayload>() {
+                POLICY.restore(&queued.ticket, payload.sender, queued.reserved, queued.queued_at);
+            }
+        }
+        tokio::spawn(deliver_queued_settlements(queue));
+    }
+
+    // Carrega a lista de revogação antes de aceitar conexões e passa a vigiá-la
+    if let Some(path) = startup_config.revocation_file.as_deref() {
+        let revoked = revocation::load_revocation_file(Path::new(path))?;
+        info!("{} token(s) revogado(s) carregado(s) de {}", revoked.len(), path);
+        revocation::apply_revocation_list(revoked);
+    }
+    tokio::spawn(revocation::watch_revocation_file(FILE_POLL_INTERVAL));
+
+    // TLS opcional no listener; o certificado é recarregado quando muda no disco
+    tls::init(startup_config.tls.as_ref())?;
+    tokio::spawn(tls::watch_certificates(FILE_POLL_INTERVAL));
+
+    // Fecha periodicamente as conexões ociosas com o Kernel
+    tokio::spawn(async {
+        let mut ticker = tokio::time::interval(FILE_POLL_INTERVAL);
+        loop {
+            ticker.tick().await;
+            for backend in balancer_for(&config::current()).backends() {
+                backend.pool.evict_idle();
+            }
+        }
+    });
+
+    // Monitor de saúde do Kernel em segundo plano
+    tokio::spawn(monitor_kernel_health());
+
+    // Hot reload do config.yaml (mudança no arquivo ou SIGHUP)
+    tokio::spawn(async {
+        if let Err(e) = config::watch_config(config::config_path().to_path_buf(), FILE_POLL_INTERVAL).await {
+            error!("Hot reload desativado: {}", e);
+        }
+    });
+
+    // Endpoint /metrics (Prometheus) opcional
+    if let Some(metrics_config) = startup_config.metrics.as_ref() {
+        let metrics_listener = TcpListener::bind(metrics_config.address.as_str()).await?;
+        info!("Métricas em http://{}/metrics", metrics_config.address);
+        tokio::spawn(async {
+            if let Err(e) = metrics::serve(metrics_listener).await {
+                error!("Endpoint de métricas encerrado: {}", e);
+            }
+        });
+    }
+
+    // API HTTP/JSON opcional, sobre o mesmo pipeline do protocolo TCP
+    if let Some(http) = startup_config.http.as_ref() {
+        let http_listener = TcpListener::bind(http.address.as_str()).await?;
+        info!("API HTTP escutando em {}", http.address);
+        tokio::spawn(async {
+            if let Err(e) = http_api::serve(http_listener).await {
+                error!("Listener HTTP encerrado: {}", e);
+            }
+        });
+    }
+
+    // Socket de administração local (sygma-admin)
+    if let Some(admin_config) = startup_config.admin.as_ref() {
+        let admin_listener = admin::bind(Path::new(&admin_config.socket))?;
+        info!("Socket de administração em {}", admin_config.socket);
+        tokio::spawn(async {
+            if let Err(e) = admin::serve(admin_listener, run_admin_command).await {
+                error!("Socket de administração encerrado: {}", e);
+            }
+        });
+    }
+
+    // SIGTERM / SIGINT: para de aceitar conexões e drena as requisições em andamento
+    tokio::spawn(async {
+        match shutdown::wait_for_signal().await {
+            Ok(signal) => {
+                info!("{} recebido. Parando de aceitar conexões.", signal);
+                shutdown::DRAIN.stop();
+            }
+            Err(e) => error!("Encerramento gracioso desativado: {}", e),
+        }
+    });
+
+    // host:porta ou unix:/caminho (socket com a permissão de `unix_socket.mode`)
+    let listener = ClientListener::bind(&startup_config.proxy_address, &startup_config.unix_socket).await?;
+    let transport = match (&listener, startup_config.tls.is_some()) {
+        (_, true) => "TLS",
+        (ClientListener::Tcp(_), false) => "TCP",
+        (ClientListener::Unix(_), false) => "socket unix",
+    };
+    info!("Sygma Proxy (Tier 2 Agent) escutando em {} via {} (YAML Config + Health Check Ativo)", startup_config.proxy_address, transport);
 
     loop {
-        let (stream, addr) = listener.accept().await?;
-        println!("PROXY: Conexão recebida de {}", addr);
-        
+        let (stream, addr, peer): (Box<dyn Connection>, _, _) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = shutdown::DRAIN.stopped() => break,
+        };
+        let source = peer.map_or_else(|| addr.to_string(), |peer| peer.to_string());
+        debug!("Conexão recebida de {}", source);
+        let limits = config::current().connections.clone();
+        let Some((slot, timer)) = connections::admit(addr.ip(), &limits) else {
+            continue;
+        };
+        let stream = TimedStream::new(stream, timer);
+        let in_flight = shutdown::DRAIN.begin();
+
         tokio::spawn(async move {
-            if let Err(e) = handle_connection(stream).await {
-                eprintln!("PROXY ERROR: Falha ao lidar com a conexão: {}", e);
+            let (_slot, _in_flight) = (slot, in_flight);
+            let result = match tls::listener() {
+                Some(listener) => match listener.accept(stream, limits.handshake_timeout()).await {
+                    Ok((tls_stream, identity)) => handle_connection(tls_stream, addr, peer, identity).await,
+                    Err(e) => {
+                        warn!("Handshake TLS com {} falhou: {}", source, e);
+                        Ok(())
+                    }
+                },
+                None => handle_connection(stream, addr, peer, None).await,
+            };
+            match result {
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => debug!("Conexão de {} encerrada: {}", source, e),
+                Err(e) => warn!("Falha ao lidar com a conexão de {}: {}", source, e),
+                Ok(()) => {}
             }
         });
     }
+    drop(listener);
+
+    // Prazo lido agora: um hot reload anterior ao sinal vale para esta drenagem
+    let drain_timeout = config::current().shutdown.drain_timeout_secs;
+    info!("Aguardando {} requisição(ões) em andamento (prazo de {}s).", shutdown::DRAIN.active(), drain_timeout);
+    if shutdown::DRAIN.wait_idle(Duration::from_secs(drain_timeout)).await {
+        info!("Requisições em andamento concluídas.");
+    } else {
+        warn!("Prazo de drenagem esgotado: {} requisição(ões) interrompida(s).", shutdown::DRAIN.active());
+    }
+    if !audit::flush(Duration::from_secs(5)).await {
+        warn!("Trilha de auditoria não foi totalmente descarregada no disco.");
+    }
+    info!("Sygma Proxy encerrado.");
+    shutdown::flush();
+    Ok(())
 }
 
 // --- BLOCO DE TESTES UNITÁRIOS E DE INTEGRAÇÃO (Rastreabilidade e Confiabilidade) ---
@@ -139,12 +953,20 @@ async fn main() -> io::Result<()> {
 mod tests {
     use super::TRUST_CACHE;
     use super::verify_zero_trust_token;
-    use super::APP_CONFIG; 
+    use super::config;
+    use super::REJECTED_CACHE;
+    use super::authenticate_token;
+    use super::token::{cache_key, request_token, signed_token};
+    use super::{audit, settle, Begin, IDEMPOTENCY};
+    use super::{read_request, MAX_REQUEST_BYTES};
+    use super::connections::{ConnectionConfig, ConnectionTimer, TimedStream};
+    use sygma_protocol::SygmaError;
+    use tokio::io::AsyncWriteExt;
     // Removendo std::time::Duration e std::thread para testes mais determinísticos.
 
     // Garante que a configuração e o cache sejam inicializados e limpos antes de qualquer teste
     fn setup() {
-        let _ = APP_CONFIG.proxy_address.as_str(); // Força a inicialização global (inclui o cache)
+        let _ = config::current(); // Força a inicialização global (inclui o cache)
         let _ = TRUST_CACHE.entry_count(); // Força acesso ao cache
         TRUST_CACHE.invalidate_all(); // LIMPEZA CHAVE
     }
@@ -154,8 +976,8 @@ mod tests {
     async fn test_verify_valid_token() {
         setup();
         // ZTC deve passar
-        let token = "AUTH_SYGMA_VALID_TEST_TOKEN"; // Corrigido para remover o _1 final
-        assert!(verify_zero_trust_token(token).await, "O token válido deve passar no ZTC.");
+        let token = signed_token("AUTH_SYGMA_VALID_TEST_TOKEN"); // Assinado com a chave do config.yaml de teste
+        assert!(verify_zero_trust_token(&token).await, "O token válido deve passar no ZTC.");
     }
 
     // Teste 2: Valida a Regra de Ouro (Zero Trust Check)
@@ -167,6 +989,23 @@ mod tests {
         assert!(!verify_zero_trust_token(token).await, "O token inválido deve falhar no ZTC.");
     }
 
+    // Teste 2b: Tokens rejeitados entram no cache negativo (e nunca no TinyLFU).
+    #[tokio::test]
+    async fn test_rejected_token_negative_cache() {
+        let token = "FRAUD_ATTEMPT_NEGATIVE_CACHE";
+        assert!(!verify_zero_trust_token(&signed_token(token)).await);
+        assert!(REJECTED_CACHE.contains_key(token), "O token rejeitado deve entrar no cache negativo.");
+        assert!(TRUST_CACHE.get(token).is_none());
+        assert!(!verify_zero_trust_token(&signed_token(token)).await, "A segunda verificação usa o cache negativo.");
+    }
+
+    // Teste 2c: Tokens com `exp` no passado são rejeitados mesmo com o prefixo válido.
+    #[tokio::test]
+    async fn test_expired_token_rejected() {
+        assert!(!verify_zero_trust_token(&signed_token("AUTH_SYGMA_VALID_EXPIRED;exp=1")).await);
+        assert!(verify_zero_trust_token(&signed_token("AUTH_SYGMA_VALID_NOT_EXPIRED;exp=99999999999")).await);
+    }
+
     // Teste 3: Prova a persistência e uso do cache TinyLFU.
     #[tokio::test]
     async fn test_caching_behavior() {
@@ -174,7 +1013,7 @@ mod tests {
         let token = "AUTH_SYGMA_VALID_CACHE_TEST"; // Token válido e claro
         
         // 1. Primeira verificação: Deve ser uma verificação LENTA e inserir o token no cache.
-        let is_valid = verify_zero_trust_token(token).await;
+        let is_valid = verify_zero_trust_token(&signed_token(token)).await;
         assert!(is_valid, "A primeira verificação de token válido deve passar.");
 
         // 2. Prova de persistência: Verifica se o token está no cache IMEDIATAMENTE após a inserção.
@@ -184,5 +1023,68 @@ mod tests {
         
         // 3. Simulação da segunda verificação: Esta chamada DEVE usar o cache.
     }
-}
 
+    // Teste 4: nonce e ts só contam assinados, e o cache guarda o token sem eles.
+    #[tokio::test]
+    async fn test_request_claims_require_signature() {
+        let first = request_token("AUTH_SYGMA_VALID_NONCE_TEST");
+        let second = request_token("AUTH_SYGMA_VALID_NONCE_TEST");
+        let claims = authenticate_token(&first).await.unwrap();
+        assert!(claims.nonce.is_some() && claims.ts.is_some());
+        assert!(authenticate_token(&second).await.is_some());
+        assert_eq!(cache_key(&first), "AUTH_SYGMA_VALID_NONCE_TEST");
+        assert!(TRUST_CACHE.get(&cache_key(&first)).is_some(), "Uma entrada por token, não por requisição.");
+        assert!(TRUST_CACHE.get(&first).is_none());
+
+        // Sem assinatura o token é recusado, e não entra no cache negativo (bloquearia o legítimo)
+        let unsigned = "AUTH_SYGMA_VALID_NONCE_TEST;nonce=1;ts=1";
+        assert!(authenticate_token(unsigned).await.is_none());
+        assert!(!REJECTED_CACHE.contains_key(&cache_key(unsigned)));
+    }
+
+    // Teste 5: A retentativa com o mesmo token e a mesma chave recebe a resposta original, não um replay.
+    #[tokio::test]
+    async fn test_idempotent_retry_reuses_nonce() {
+        let payload = "ZKP_HASH_S:1_R:2_A:3";
+        let Begin::New(pending) = IDEMPOTENCY.begin("IDEMPOTENT_RETRY", "retry-1", payload) else {
+            panic!("Chave nova.");
+        };
+        pending.complete("200 OK: Payload liquidado");
+
+        let config = config::current();
+        let addr: std::net::SocketAddr = "127.0.0.1:9".parse().unwrap();
+        let token = request_token("AUTH_SYGMA_VALID_IDEMPOTENT_RETRY");
+        for _ in 0..2 {
+            let mut entry = audit::Entry::new("tcp", addr.to_string(), "cid".to_string());
+            let outcome = settle(&config, addr, None, &token, payload, Some("retry-1"), &mut entry).await;
+            assert_eq!(outcome.response, "200 OK: Payload liquidado");
+            assert!(outcome.replayed);
+        }
+    }
+
+    // Teste 6: A requisição TCP vai até o `\n`, com tamanho máximo e dentro do prazo da fase Request.
+    #[tokio::test]
+    async fn test_read_request_line() {
+        let mut line: &[u8] = b"AUTH_SYGMA_VALID_x|ZKP_HASH_S:1_R:2_A:3|k1\nlixo";
+        assert_eq!(read_request(&mut line).await.unwrap(), Ok("AUTH_SYGMA_VALID_x|ZKP_HASH_S:1_R:2_A:3|k1".to_string()));
+        let mut unterminated: &[u8] = b"HEALTH";
+        assert_eq!(read_request(&mut unterminated).await.unwrap(), Ok("HEALTH".to_string()), "O fim da conexão também encerra a requisição.");
+        let oversized = vec![b'A'; MAX_REQUEST_BYTES + 1];
+        assert_eq!(read_request(&mut oversized.as_slice()).await.unwrap(), Err(SygmaError::PayloadTooLarge { limit_bytes: MAX_REQUEST_BYTES }));
+        assert_eq!(SygmaError::PayloadTooLarge { limit_bytes: MAX_REQUEST_BYTES }.to_string(), "PAYLOAD TOO LARGE: Request exceeds 8192 bytes");
+
+        // Requisição que chega em partes: junta tudo; parada no meio, recebe o 408
+        let config = ConnectionConfig { request_timeout_ms: 100, ..ConnectionConfig::default() };
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut stream = TimedStream::new(server, ConnectionTimer::new(&config));
+        client.write_all(b"AUTH_SYGMA_VALID_x|").await.unwrap();
+        let reading = tokio::spawn(async move { read_request(&mut stream).await.unwrap() });
+        client.write_all(b"ZKP_HASH_S:1_R:2_A:3\n").await.unwrap();
+        assert_eq!(reading.await.unwrap(), Ok("AUTH_SYGMA_VALID_x|ZKP_HASH_S:1_R:2_A:3".to_string()));
+
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut stream = TimedStream::new(server, ConnectionTimer::new(&config));
+        client.write_all(b"AUTH_SYGMA_VALID_x|ZKP").await.unwrap();
+        assert_eq!(read_request(&mut stream).await.unwrap(), Err(SygmaError::RequestTimeout));
+    }
+}
@@ -0,0 +1,195 @@
+// sygma_proxy/src/metrics.rs - Métricas do Proxy (Tier 2) no formato texto do Prometheus
+//
+// GET /metrics no endereço da seção `metrics` do config.yaml.
+
+use std::convert::Infallible;
+use std::io;
+use std::time::Duration;
+
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::header::CONTENT_TYPE;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+use serde::Deserialize;
+use tokio::net::TcpListener;
+use tracing::warn;
+
+// --- SEÇÃO `metrics` DO config.yaml ---
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MetricsConfig {
+    // Endereço do endpoint /metrics (ex.: 127.0.0.1:9179)
+    pub address: String,
+}
+
+pub struct ProxyMetrics {
+    registry: Registry,
+    // Requisições por listener (tcp | http) e código de resposta (200, 400, 403, 503...)
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    // Consultas ao TRUST_CACHE (hit | miss)
+    trust_cache: IntCounterVec,
+    // Sondas de saúde por backend e resultado (success | failure)
+    probes: IntCounterVec,
+    probe_duration: HistogramVec,
+    // Ida e volta de um SETTLE ao Kernel, por backend
+    kernel_duration: HistogramVec,
+    // Conexões de clientes abertas, recusadas pelos limites (global_limit | ip_limit)
+    // e encerradas por timeout (handshake | first_byte | request | idle)
+    open_connections: IntGauge,
+    rejected_connections: IntCounterVec,
+    connection_timeouts: IntCounterVec,
+    // Linhas da trilha de auditoria enfileiradas (queued) ou descartadas com a fila cheia (dropped)
+    audit_records: IntCounterVec,
+}
+
+fn counter(name: &str, help: &str, labels: &[&str]) -> IntCounterVec {
+    IntCounterVec::new(Opts::new(name, help), labels).unwrap()
+}
+
+fn histogram(name: &str, help: &str, labels: &[&str]) -> HistogramVec {
+    HistogramVec::new(HistogramOpts::new(name, help), labels).unwrap()
+}
+
+impl ProxyMetrics {
+    fn new() -> Self {
+        let metrics = ProxyMetrics {
+            registry: Registry::new(),
+            requests: counter("sygma_proxy_requests_total", "Requisições por listener e código de resposta", &["listener", "code"]),
+            request_duration: histogram("sygma_proxy_request_duration_seconds", "Tempo de resposta do Proxy", &["listener"]),
+            trust_cache: counter("sygma_proxy_trust_cache_lookups_total", "Consultas ao TRUST_CACHE por resultado", &["result"]),
+            probes: counter("sygma_proxy_health_probes_total", "Sondas de saúde do Kernel por resultado", &["backend", "result"]),
+            probe_duration: histogram("sygma_proxy_health_probe_duration_seconds", "Tempo das sondas de saúde do Kernel", &["backend"]),
+            kernel_duration: histogram("sygma_proxy_kernel_request_duration_seconds", "Tempo de um Settlement no Kernel", &["backend"]),
+            open_connections: IntGauge::new("sygma_proxy_open_connections", "Conexões de clientes abertas").unwrap(),
+            rejected_connections: counter("sygma_proxy_rejected_connections_total", "Conexões recusadas pelos limites", &["reason"]),
+            connection_timeouts: counter("sygma_proxy_connection_timeouts_total", "Conexões encerradas por timeout, por fase", &["phase"]),
+            audit_records: counter("sygma_proxy_audit_records_total", "Linhas da trilha de auditoria por resultado", &["result"]),
+        };
+        metrics.registry.register(Box::new(metrics.open_connections.clone())).unwrap();
+        for collector in [&metrics.requests, &metrics.trust_cache, &metrics.probes, &metrics.rejected_connections, &metrics.connection_timeouts, &metrics.audit_records] {
+            metrics.registry.register(Box::new(collector.clone())).unwrap();
+        }
+        for collector in [&metrics.request_duration, &metrics.probe_duration, &metrics.kernel_duration] {
+            metrics.registry.register(Box::new(collector.clone())).unwrap();
+        }
+        metrics
+    }
+
+    // `code` é o status da resposta ("403 ACCESS DENIED..." -> 403)
+    pub fn record_request(&self, listener: &str, code: u16, elapsed: Duration) {
+        self.requests.with_label_values(&[listener, &code.to_string()]).inc();
+        self.request_duration.with_label_values(&[listener]).observe(elapsed.as_secs_f64());
+    }
+
+    pub fn record_trust_cache(&self, hit: bool) {
+        self.trust_cache.with_label_values(&[if hit { "hit" } else { "miss" }]).inc();
+    }
+
+    // (hits, misses) do TRUST_CACHE desde o início do processo
+    pub fn trust_cache_lookups(&self) -> (u64, u64) {
+        (self.trust_cache.with_label_values(&["hit"]).get(), self.trust_cache.with_label_values(&["miss"]).get())
+    }
+
+    pub fn record_probe(&self, backend: &str, success: bool, elapsed: Duration) {
+        self.probes.with_label_values(&[backend, if success { "success" } else { "failure" }]).inc();
+        self.probe_duration.with_label_values(&[backend]).observe(elapsed.as_secs_f64());
+    }
+
+    pub fn record_kernel_request(&self, backend: &str, elapsed: Duration) {
+        self.kernel_duration.with_label_values(&[backend]).observe(elapsed.as_secs_f64());
+    }
+
+    pub fn set_open_connections(&self, open: usize) {
+        self.open_connections.set(open as i64);
+    }
+
+    pub fn record_connection_rejected(&self, reason: &str) {
+        self.rejected_connections.with_label_values(&[reason]).inc();
+    }
+
+    pub fn record_connection_timeout(&self, phase: &str) {
+        self.connection_timeouts.with_label_values(&[phase]).inc();
+    }
+
+    pub fn record_audit(&self, queued: bool) {
+        self.audit_records.with_label_values(&[if queued { "queued" } else { "dropped" }]).inc();
+    }
+
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+lazy_static! {
+    pub static ref METRICS: ProxyMetrics = ProxyMetrics::new();
+}
+
+// Código numérico de uma resposta do protocolo ("503 SERVICE UNAVAILABLE..." -> 503)
+pub fn status_code(response: &str) -> u16 {
+    response.get(..3).and_then(|code| code.parse().ok()).unwrap_or(0)
+}
+
+async fn scrape(request: Request<Incoming>) -> Result<Response<Full<Bytes>>, Infallible> {
+    let mut response = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/metrics") => Response::new(Full::new(Bytes::from(METRICS.render()))),
+        _ => {
+            let mut response = Response::new(Full::new(Bytes::from_static(b"not found\n")));
+            *response.status_mut() = StatusCode::NOT_FOUND;
+            response
+        }
+    };
+    response.headers_mut().insert(CONTENT_TYPE, "text/plain; version=0.0.4".parse().unwrap());
+    Ok(response)
+}
+
+pub async fn serve(listener: TcpListener) -> io::Result<()> {
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        tokio::spawn(async move {
+            if let Err(e) = http1::Builder::new().serve_connection(TokioIo::new(stream), service_fn(scrape)).await {
+                warn!("Falha ao servir /metrics para {}: {}", addr, e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Teste 1: Contadores e histogramas registrados aparecem no texto do Prometheus.
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        METRICS.record_request("tcp", status_code("403 ACCESS DENIED: Zero Trust Violation"), Duration::from_millis(3));
+        METRICS.record_trust_cache(false);
+        METRICS.record_probe("127.0.0.1:8080", true, Duration::from_millis(1));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener));
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: proxy\r\nConnection: close\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("sygma_proxy_requests_total{code=\"403\",listener=\"tcp\"}"));
+        assert!(response.contains("sygma_proxy_trust_cache_lookups_total{result=\"miss\"}"));
+        assert!(response.contains("sygma_proxy_health_probes_total{backend=\"127.0.0.1:8080\",result=\"success\"}"));
+        assert!(response.contains("sygma_proxy_request_duration_seconds_bucket"));
+    }
+
+    #[test]
+    fn test_status_code() {
+        assert_eq!(status_code("200 OK: Payload liquidado"), 200);
+        assert_eq!(status_code("??"), 0);
+    }
+}
@@ -0,0 +1,173 @@
+// sygma_proxy/src/payload.rs - Payload de Settlement Tipado
+//
+// Formato: ZKP_HASH_S:<remetente>_R:<destinatário>_A:<valor>, os três em u64.
+// Ex.: ZKP_HASH_S:123_R:456_A:789
+// O Proxy só encaminha ao Kernel payloads válidos, sempre na forma canônica (`Display`);
+// os demais recebem um 400 que diz qual campo está ausente, fora do intervalo ou não numérico.
+
+use std::fmt;
+use std::num::IntErrorKind;
+use std::str::FromStr;
+
+use sygma_protocol::SygmaError;
+
+const PAYLOAD_PREFIX: &str = "ZKP_HASH_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SettlementPayload {
+    pub sender: u64,
+    pub receiver: u64,
+    pub amount: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Sender,
+    Receiver,
+    Amount,
+}
+
+impl Field {
+    const ALL: [Field; 3] = [Field::Sender, Field::Receiver, Field::Amount];
+
+    fn tag(self) -> &'static str {
+        match self {
+            Field::Sender => "S",
+            Field::Receiver => "R",
+            Field::Amount => "A",
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Field::Sender => "sender",
+            Field::Receiver => "receiver",
+            Field::Amount => "amount",
+        };
+        write!(f, "{} ({})", self.tag(), name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PayloadError {
+    // Não começa com ZKP_HASH_ (ou está vazio)
+    UnknownFormat,
+    Missing(Field),
+    NotNumeric(Field),
+    OutOfRange(Field),
+    // Campo fora de S/R/A, repetido ou fora de ordem
+    Unexpected(String),
+    SameAccount,
+}
+
+impl fmt::Display for PayloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PayloadError::UnknownFormat => write!(f, "expected ZKP_HASH_S:<sender>_R:<receiver>_A:<amount>"),
+            PayloadError::Missing(field) => write!(f, "field {} is missing", field),
+            PayloadError::NotNumeric(field) => write!(f, "field {} is not numeric", field),
+            PayloadError::OutOfRange(field) => match field {
+                Field::Amount => write!(f, "field {} is out of range (1..={})", field, u64::MAX),
+                _ => write!(f, "field {} is out of range (0..={})", field, u64::MAX),
+            },
+            PayloadError::Unexpected(segment) => write!(f, "unexpected field '{}'", segment),
+            PayloadError::SameAccount => write!(f, "sender and receiver are the same account"),
+        }
+    }
+}
+
+impl PayloadError {
+    // Erro do protocolo (400, código INVALID_PAYLOAD) com o motivo preciso
+    pub fn error(&self) -> SygmaError {
+        SygmaError::InvalidPayload(self.to_string())
+    }
+}
+
+fn parse_field(field: Field, value: &str) -> Result<u64, PayloadError> {
+    // `u64::from_str` aceita "+5"; o formato não
+    if value.starts_with('+') {
+        return Err(PayloadError::NotNumeric(field));
+    }
+    let number = value.parse::<u64>().map_err(|e| match e.kind() {
+        IntErrorKind::Empty => PayloadError::Missing(field),
+        IntErrorKind::PosOverflow => PayloadError::OutOfRange(field),
+        _ => PayloadError::NotNumeric(field),
+    })?;
+    if field == Field::Amount && number == 0 {
+        return Err(PayloadError::OutOfRange(field));
+    }
+    Ok(number)
+}
+
+impl FromStr for SettlementPayload {
+    type Err = PayloadError;
+
+    fn from_str(payload: &str) -> Result<Self, Self::Err> {
+        let body = payload.strip_prefix(PAYLOAD_PREFIX).ok_or(PayloadError::UnknownFormat)?;
+        let mut segments = body.split('_');
+        let mut values = [0u64; 3];
+
+        for (slot, field) in values.iter_mut().zip(Field::ALL) {
+            let segment = segments.next().filter(|segment| !segment.is_empty()).ok_or(PayloadError::Missing(field))?;
+            let (tag, value) = segment.split_once(':').ok_or_else(|| PayloadError::Unexpected(segment.to_string()))?;
+            if tag != field.tag() {
+                // Outro campo conhecido no lugar deste: o esperado não veio
+                let known = Field::ALL.iter().any(|other| other.tag() == tag);
+                return Err(if known { PayloadError::Missing(field) } else { PayloadError::Unexpected(segment.to_string()) });
+            }
+            *slot = parse_field(field, value)?;
+        }
+        if let Some(extra) = segments.next() {
+            return Err(PayloadError::Unexpected(extra.to_string()));
+        }
+
+        let [sender, receiver, amount] = values;
+        if sender == receiver {
+            return Err(PayloadError::SameAccount);
+        }
+        Ok(SettlementPayload { sender, receiver, amount })
+    }
+}
+
+// Forma canônica enviada ao Kernel
+impl fmt::Display for SettlementPayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}S:{}_R:{}_A:{}", PAYLOAD_PREFIX, self.sender, self.receiver, self.amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reason(payload: &str) -> String {
+        payload.parse::<SettlementPayload>().unwrap_err().error().to_string()
+    }
+
+    // Teste 1: Payload válido vira a struct tipada e volta à forma canônica.
+    #[test]
+    fn test_parse_valid_payload() {
+        let payload: SettlementPayload = "ZKP_HASH_S:123_R:456_A:789".parse().unwrap();
+        assert_eq!(payload, SettlementPayload { sender: 123, receiver: 456, amount: 789 });
+        assert_eq!(payload.to_string(), "ZKP_HASH_S:123_R:456_A:789");
+        assert_eq!("ZKP_HASH_S:007_R:8_A:9".parse::<SettlementPayload>().unwrap().to_string(), "ZKP_HASH_S:7_R:8_A:9");
+    }
+
+    // Teste 2: Cada rejeição diz qual campo está errado e por quê.
+    #[test]
+    fn test_precise_rejections() {
+        assert_eq!(reason("OUTRO_PAYLOAD"), "INVALID PAYLOAD: expected ZKP_HASH_S:<sender>_R:<receiver>_A:<amount>");
+        assert_eq!(reason("ZKP_HASH_S:1_A:3"), "INVALID PAYLOAD: field R (receiver) is missing");
+        assert_eq!(reason("ZKP_HASH_S:1_R:2"), "INVALID PAYLOAD: field A (amount) is missing");
+        assert_eq!(reason("ZKP_HASH_S:_R:2_A:3"), "INVALID PAYLOAD: field S (sender) is missing");
+        assert_eq!(reason("ZKP_HASH_S:1_R:dois_A:3"), "INVALID PAYLOAD: field R (receiver) is not numeric");
+        assert_eq!(reason("ZKP_HASH_S:-1_R:2_A:3"), "INVALID PAYLOAD: field S (sender) is not numeric");
+        assert_eq!(reason("ZKP_HASH_S:1_R:2_A:+3"), "INVALID PAYLOAD: field A (amount) is not numeric");
+        assert!(reason("ZKP_HASH_S:1_R:2_A:18446744073709551616").contains("field A (amount) is out of range"));
+        assert!(reason("ZKP_HASH_S:1_R:2_A:0").contains("field A (amount) is out of range (1..="));
+        assert_eq!(reason("ZKP_HASH_S:1_R:2_A:3_X:4"), "INVALID PAYLOAD: unexpected field 'X:4'");
+        assert_eq!(reason("ZKP_HASH_S:5_R:5_A:3"), "INVALID PAYLOAD: sender and receiver are the same account");
+    }
+}
@@ -0,0 +1,357 @@
+// sygma_proxy/src/policy.rs - Regras de Negócio Declarativas (seção `policy` do config.yaml)
+//
+// Avaliadas no Proxy antes de qualquer encaminhamento ao Kernel:
+//   - teto por transação (`max_amount`)
+//   - teto diário acumulado por conta de origem (`daily_limit_per_sender`, dia UTC)
+//   - listas de destinatários permitidos / proibidos
+//   - limites por escopo do token (claim `scope=a,b`)
+// As regras são lidas do snapshot da configuração a cada requisição (hot reload); os totais
+// diários vivem só em memória, num cache limitado como os baldes do rate limit: um reinício, ou
+// mais de MAX_TRACKED_SENDERS contas ativas no dia, zera o total de uma conta (exceto o que está
+// reservado na fila store-and-forward, recontado na inicialização).
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use moka::sync::Cache;
+use serde::Deserialize;
+use sygma_protocol::SygmaError;
+
+use crate::payload::SettlementPayload;
+use crate::token::TokenClaims;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+// Contas de origem com total diário rastreado simultaneamente
+const MAX_TRACKED_SENDERS: u64 = 100_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Limits {
+    // Valor máximo de uma transação
+    pub max_amount: Option<u64>,
+    // Soma máxima liquidada por conta de origem no dia (UTC)
+    pub daily_limit_per_sender: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ReceiverLists {
+    // Vazia = qualquer destinatário
+    pub allow: Vec<u64>,
+    pub deny: Vec<u64>,
+}
+
+// --- SEÇÃO `policy` DO config.yaml ---
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct PolicyConfig {
+    #[serde(flatten)]
+    pub limits: Limits,
+    pub receivers: ReceiverLists,
+    // Limites por escopo do token. Um token com vários escopos usa o mais permissivo deles;
+    // os limites globais acima valem sempre.
+    pub scopes: HashMap<String, Limits>,
+}
+
+impl PolicyConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        let limits = std::iter::once(("policy", &self.limits))
+            .chain(self.scopes.iter().map(|(name, limits)| (name.as_str(), limits)));
+        for (name, limits) in limits {
+            if limits.max_amount == Some(0) || limits.daily_limit_per_sender == Some(0) {
+                return Err(format!("{}: max_amount e daily_limit_per_sender devem ser maiores que zero", name));
+            }
+        }
+        if let Some(receiver) = self.receivers.allow.iter().find(|receiver| self.receivers.deny.contains(receiver)) {
+            return Err(format!("policy.receivers: conta {} está em allow e em deny", receiver));
+        }
+        Ok(())
+    }
+
+    // Limite aplicável ao token e o escopo que o define (`None` = o limite global).
+    // Vale o escopo mais permissivo do token, nunca acima do limite global.
+    fn effective(&self, scopes: &[String], limit: fn(&Limits) -> Option<u64>) -> Option<(u64, Option<&str>)> {
+        let global = limit(&self.limits).map(|value| (value, None));
+        let mut scoped: Option<(u64, &str)> = None;
+        for (name, limits) in scopes.iter().filter_map(|scope| self.scopes.get_key_value(scope.as_str())) {
+            match limit(limits) {
+                // Um escopo sem este limite não restringe o token
+                None => return global,
+                Some(value) if scoped.is_none_or(|(current, _)| value > current) => scoped = Some((value, name)),
+                Some(_) => {}
+            }
+        }
+        match (global, scoped) {
+            (Some((global_value, _)), Some((value, _))) if global_value <= value => global,
+            (_, Some((value, name))) => Some((value, Some(name))),
+            (global, None) => global,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Denial {
+    AmountCap { amount: u64, limit: u64, scope: Option<String> },
+    DailyCap { sender: u64, used: u64, amount: u64, limit: u64, scope: Option<String> },
+    ReceiverDenied(u64),
+    ReceiverNotAllowed(u64),
+}
+
+impl Denial {
+    pub fn error(&self) -> SygmaError {
+        let scoped = |scope: &Option<String>| scope.as_ref().map(|scope| format!(" for scope '{}'", scope)).unwrap_or_default();
+        let reason = match self {
+            Denial::AmountCap { amount, limit, scope } => {
+                format!("amount {} exceeds max_amount {}{}", amount, limit, scoped(scope))
+            }
+            Denial::DailyCap { sender, used, amount, limit, scope } => format!(
+                "daily limit {}{} exceeded for sender {} ({} already settled today, {} requested)",
+                limit, scoped(scope), sender, used, amount
+            ),
+            Denial::ReceiverDenied(receiver) => format!("receiver {} is on the deny list", receiver),
+            Denial::ReceiverNotAllowed(receiver) => format!("receiver {} is not on the allow list", receiver),
+        };
+        SygmaError::PolicyDenied(reason)
+    }
+}
+
+// Total liquidado por uma conta num dia (UTC, em dias desde a época Unix)
+#[derive(Debug)]
+struct DailyTotal {
+    day: u64,
+    total: u64,
+}
+
+pub struct PolicyEngine {
+    daily: Cache<u64, Arc<Mutex<DailyTotal>>>,
+    // Reservas dos Settlements na fila store-and-forward, por ticket, até o resultado da entrega
+    held: Mutex<HashMap<String, Reservation>>,
+}
+
+// Valor reservado no total diário da conta. Sem `commit`, a reserva é desfeita ao sair de
+// escopo (o Kernel rejeitou ou nunca recebeu a transação).
+#[derive(Debug)]
+pub struct Reservation {
+    total: Option<Arc<Mutex<DailyTotal>>>,
+    day: u64,
+    amount: u64,
+}
+
+impl Reservation {
+    // Valor contado no total diário (0 quando nenhum teto diário se aplica)
+    pub fn amount(&self) -> u64 {
+        self.total.as_ref().map_or(0, |_| self.amount)
+    }
+
+    pub fn commit(mut self) {
+        self.total = None;
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if let Some(total) = self.total.take() {
+            let mut total = total.lock().unwrap();
+            if total.day == self.day {
+                total.total = total.total.saturating_sub(self.amount);
+            }
+        }
+    }
+}
+
+impl Default for PolicyEngine {
+    fn default() -> Self {
+        PolicyEngine {
+            daily: Cache::builder()
+                .max_capacity(MAX_TRACKED_SENDERS)
+                .time_to_idle(Duration::from_secs(SECONDS_PER_DAY))
+                .build(),
+            held: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl PolicyEngine {
+    // Avalia as regras e, se aprovada, reserva o valor no total diário da conta de origem
+    pub fn check(&self, policy: &PolicyConfig, claims: &TokenClaims, payload: &SettlementPayload, now_unix: u64) -> Result<Reservation, Denial> {
+        let receivers = &policy.receivers;
+        if receivers.deny.contains(&payload.receiver) {
+            return Err(Denial::ReceiverDenied(payload.receiver));
+        }
+        if !receivers.allow.is_empty() && !receivers.allow.contains(&payload.receiver) {
+            return Err(Denial::ReceiverNotAllowed(payload.receiver));
+        }
+
+        let scope_name = |scope: Option<&str>| scope.map(str::to_string);
+        if let Some((limit, scope)) = policy.effective(&claims.scopes, |limits| limits.max_amount) {
+            if payload.amount > limit {
+                return Err(Denial::AmountCap { amount: payload.amount, limit, scope: scope_name(scope) });
+            }
+        }
+
+        let Some((limit, scope)) = policy.effective(&claims.scopes, |limits| limits.daily_limit_per_sender) else {
+            return Ok(Reservation { total: None, day: 0, amount: 0 });
+        };
+        let day = now_unix / SECONDS_PER_DAY;
+        let entry = self.daily.get_with(payload.sender, || Arc::new(Mutex::new(DailyTotal { day, total: 0 })));
+        let mut total = entry.lock().unwrap();
+        if total.day != day {
+            *total = DailyTotal { day, total: 0 };
+        }
+        if total.total.saturating_add(payload.amount) > limit {
+            return Err(Denial::DailyCap { sender: payload.sender, used: total.total, amount: payload.amount, limit, scope: scope_name(scope) });
+        }
+        total.total += payload.amount;
+        drop(total);
+        Ok(Reservation { total: Some(entry), day, amount: payload.amount })
+    }
+
+    // Settlement posto na fila: a reserva continua contando até `resolve` com o resultado da entrega
+    pub fn hold(&self, ticket: &str, reservation: Reservation) {
+        self.held.lock().unwrap().insert(ticket.to_string(), reservation);
+    }
+
+    // Ticket recuperado da fila num reinício: volta a contar o valor reservado no dia em que
+    // foi aceito, até `resolve`
+    pub fn restore(&self, ticket: &str, sender: u64, amount: u64, queued_at_unix: u64) {
+        if amount == 0 {
+            return;
+        }
+        let day = queued_at_unix / SECONDS_PER_DAY;
+        let entry = self.daily.get_with(sender, || Arc::new(Mutex::new(DailyTotal { day, total: 0 })));
+        let mut total = entry.lock().unwrap();
+        if total.day < day {
+            *total = DailyTotal { day, total: 0 };
+        }
+        // De um dia já encerrado, a reserva não conta mais
+        if total.day == day {
+            total.total += amount;
+        }
+        drop(total);
+        self.hold(ticket, Reservation { total: Some(entry), day, amount });
+    }
+
+    // Entrega de um ticket concluída: confirma a reserva (`settled`) ou a devolve ao saldo do dia
+    pub fn resolve(&self, ticket: &str, settled: bool) {
+        let reservation = self.held.lock().unwrap().remove(ticket);
+        if let Some(reservation) = reservation.filter(|_| settled) {
+            reservation.commit();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_760_000_000;
+
+    fn policy(yaml: &str) -> PolicyConfig {
+        let policy: PolicyConfig = serde_yaml::from_str(yaml).unwrap();
+        policy.validate().unwrap();
+        policy
+    }
+
+    fn claims(scopes: &[&str]) -> TokenClaims {
+        let token = format!("AUTH_SYGMA_VALID_conta42;scope={}", scopes.join(","));
+        TokenClaims::parse(&token).unwrap()
+    }
+
+    fn payload(sender: u64, receiver: u64, amount: u64) -> SettlementPayload {
+        SettlementPayload { sender, receiver, amount }
+    }
+
+    // Teste 1: Teto por transação e listas de destinatários, com o motivo de cada negação.
+    #[test]
+    fn test_amount_cap_and_receiver_lists() {
+        let engine = PolicyEngine::default();
+        let rules = policy("max_amount: 1000\nreceivers:\n  deny: [666]\n");
+        assert!(engine.check(&rules, &claims(&[]), &payload(1, 2, 1000), NOW).is_ok());
+        assert_eq!(
+            engine.check(&rules, &claims(&[]), &payload(1, 2, 1001), NOW).unwrap_err().error().to_string(),
+            "POLICY DENIED: amount 1001 exceeds max_amount 1000"
+        );
+        assert_eq!(engine.check(&rules, &claims(&[]), &payload(1, 666, 1), NOW).unwrap_err(), Denial::ReceiverDenied(666));
+
+        let allow_only = policy("receivers:\n  allow: [2, 3]\n");
+        assert!(engine.check(&allow_only, &claims(&[]), &payload(1, 3, 5), NOW).is_ok());
+        assert_eq!(engine.check(&allow_only, &claims(&[]), &payload(1, 4, 5), NOW).unwrap_err(), Denial::ReceiverNotAllowed(4));
+        assert!(serde_yaml::from_str::<PolicyConfig>("receivers: { allow: [2], deny: [2] }").unwrap().validate().is_err());
+    }
+
+    // Teste 2: Teto diário acumulado por conta; reservas não confirmadas são devolvidas e o total zera no dia seguinte.
+    #[test]
+    fn test_daily_limit() {
+        let engine = PolicyEngine::default();
+        let rules = policy("daily_limit_per_sender: 100\n");
+        engine.check(&rules, &claims(&[]), &payload(7, 2, 60), NOW).unwrap().commit();
+
+        let denied = engine.check(&rules, &claims(&[]), &payload(7, 2, 50), NOW).unwrap_err();
+        assert_eq!(denied.error().to_string(), "POLICY DENIED: daily limit 100 exceeded for sender 7 (60 already settled today, 50 requested)");
+        assert!(engine.check(&rules, &claims(&[]), &payload(8, 2, 50), NOW).is_ok(), "O teto é por conta de origem.");
+
+        // Transação rejeitada pelo Kernel: a reserva volta ao saldo do dia
+        drop(engine.check(&rules, &claims(&[]), &payload(7, 2, 40), NOW).unwrap());
+        engine.check(&rules, &claims(&[]), &payload(7, 2, 40), NOW).unwrap().commit();
+        assert!(engine.check(&rules, &claims(&[]), &payload(7, 2, 1), NOW).is_err());
+
+        assert!(engine.check(&rules, &claims(&[]), &payload(7, 2, 100), NOW + SECONDS_PER_DAY).is_ok());
+    }
+
+    // Teste 3: Limites por escopo restringem os globais; vale o escopo mais permissivo do token.
+    #[test]
+    fn test_scope_limits() {
+        let engine = PolicyEngine::default();
+        let rules = policy("max_amount: 10000\nscopes:\n  retail: { max_amount: 100 }\n  payroll: { max_amount: 5000 }\n  unlimited: {}\n");
+
+        assert_eq!(
+            engine.check(&rules, &claims(&["retail"]), &payload(1, 2, 101), NOW).unwrap_err().error().to_string(),
+            "POLICY DENIED: amount 101 exceeds max_amount 100 for scope 'retail'"
+        );
+        assert!(engine.check(&rules, &claims(&["retail", "payroll"]), &payload(1, 2, 5000), NOW).is_ok());
+        assert!(engine.check(&rules, &claims(&["unlimited"]), &payload(1, 2, 10001), NOW).is_err(), "O teto global vale sempre.");
+        assert!(engine.check(&rules, &claims(&["desconhecido"]), &payload(1, 2, 10000), NOW).is_ok());
+        assert_eq!(
+            engine.check(&rules, &claims(&["payroll", "unlimited"]), &payload(1, 2, 10001), NOW).unwrap_err().error().to_string(),
+            "POLICY DENIED: amount 10001 exceeds max_amount 10000"
+        );
+    }
+
+    // Teste 4: A reserva de um Settlement na fila vale até a entrega; um 422 posterior a devolve.
+    #[test]
+    fn test_held_reservation() {
+        let engine = PolicyEngine::default();
+        let rules = policy("daily_limit_per_sender: 100\n");
+        engine.hold("tkt_19a2b3c4d5e0001", engine.check(&rules, &claims(&[]), &payload(7, 2, 60), NOW).unwrap());
+        engine.hold("tkt_19a2b3c4d5e0002", engine.check(&rules, &claims(&[]), &payload(7, 2, 40), NOW).unwrap());
+        assert!(engine.check(&rules, &claims(&[]), &payload(7, 2, 1), NOW).is_err(), "Na fila, o valor já conta.");
+
+        engine.resolve("tkt_19a2b3c4d5e0001", true);
+        engine.resolve("tkt_19a2b3c4d5e0002", false);
+        assert!(engine.check(&rules, &claims(&[]), &payload(7, 2, 40), NOW).is_ok());
+        assert!(engine.check(&rules, &claims(&[]), &payload(7, 2, 41), NOW).is_err());
+        engine.resolve("tkt_desconhecido", false);
+    }
+
+    // Teste 5: Tickets recuperados num reinício voltam a contar no teto diário até a entrega.
+    #[test]
+    fn test_restored_reservation() {
+        let engine = PolicyEngine::default();
+        let rules = policy("daily_limit_per_sender: 100\n");
+        let reserved = engine.check(&rules, &claims(&[]), &payload(7, 2, 60), NOW).unwrap().amount();
+        assert_eq!(reserved, 60);
+
+        // Outro processo: só o que foi gravado com o ticket
+        let engine = PolicyEngine::default();
+        engine.restore("tkt_19a2b3c4d5e0001", 7, reserved, NOW);
+        engine.restore("tkt_19a2b3c4d5e0002", 7, 30, NOW - SECONDS_PER_DAY);
+        assert!(engine.check(&rules, &claims(&[]), &payload(7, 2, 41), NOW).is_err(), "O recuperado conta no dia.");
+        assert!(engine.check(&rules, &claims(&[]), &payload(7, 2, 40), NOW).is_ok(), "O de ontem não conta.");
+
+        engine.resolve("tkt_19a2b3c4d5e0001", false);
+        assert!(engine.check(&rules, &claims(&[]), &payload(7, 2, 60), NOW).is_ok());
+        engine.resolve("tkt_19a2b3c4d5e0002", false);
+    }
+}
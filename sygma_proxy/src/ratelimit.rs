@@ -0,0 +1,168 @@
+// sygma_proxy/src/ratelimit.rs - Rate Limiting por Token (subject) e por IP (token bucket)
+//
+// Cada chave tem um balde com `capacity` fichas que se recarrega a `refill_per_sec`.
+// Os limites são lidos do snapshot da configuração a cada requisição (hot reload);
+// o estado dos baldes vive num cache limitado, então a memória não cresce sem limite.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use moka::sync::Cache;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct BucketConfig {
+    // Rajada máxima
+    pub capacity: u32,
+    // Fichas repostas por segundo (taxa sustentada)
+    pub refill_per_sec: f64,
+}
+
+// --- SEÇÃO `rate_limit` DO config.yaml ---
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    // Limite por IP de origem, aplicado antes do Zero-Trust Check
+    pub per_ip: BucketConfig,
+    // Limite por subject para tokens sem `tier` (ou com tier desconhecido)
+    pub per_token: BucketConfig,
+    // Limites por classe de token (claim `tier=<nome>`); exige auth.hmac_key
+    pub tiers: HashMap<String, BucketConfig>,
+    // Baldes rastreados simultaneamente e tempo ocioso até o descarte (exigem reinício)
+    pub max_tracked_keys: u64,
+    pub idle_ttl_secs: u64,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            enabled: true,
+            per_ip: BucketConfig { capacity: 50, refill_per_sec: 20.0 },
+            per_token: BucketConfig { capacity: 20, refill_per_sec: 5.0 },
+            tiers: HashMap::new(),
+            max_tracked_keys: 100_000,
+            idle_ttl_secs: 600,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    // Limite aplicável a um token, segundo a sua classe
+    pub fn for_tier(&self, tier: Option<&str>) -> &BucketConfig {
+        tier.and_then(|tier| self.tiers.get(tier)).unwrap_or(&self.per_token)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let limits = [("per_ip", &self.per_ip), ("per_token", &self.per_token)].into_iter()
+            .chain(self.tiers.iter().map(|(name, limit)| (name.as_str(), limit)));
+        for (name, limit) in limits {
+            if limit.capacity == 0 || !limit.refill_per_sec.is_finite() || limit.refill_per_sec <= 0.0 {
+                return Err(format!("rate_limit.{}: capacity e refill_per_sec devem ser maiores que zero", name));
+            }
+        }
+        if self.max_tracked_keys == 0 {
+            return Err("rate_limit.max_tracked_keys deve ser maior que zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RateKey {
+    Ip(IpAddr),
+    Subject(String),
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+pub struct RateLimiter {
+    buckets: Cache<RateKey, Arc<Mutex<Bucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        RateLimiter {
+            buckets: Cache::builder()
+                .max_capacity(config.max_tracked_keys)
+                .time_to_idle(Duration::from_secs(config.idle_ttl_secs))
+                .build(),
+        }
+    }
+
+    // Consome uma ficha. `Err` traz quanto tempo esperar até haver uma ficha disponível.
+    pub fn check(&self, key: RateKey, limit: &BucketConfig) -> Result<(), Duration> {
+        self.check_at(key, limit, Instant::now())
+    }
+
+    fn check_at(&self, key: RateKey, limit: &BucketConfig, now: Instant) -> Result<(), Duration> {
+        let capacity = f64::from(limit.capacity);
+        let bucket = self.buckets.get_with(key, || Arc::new(Mutex::new(Bucket { tokens: capacity, updated_at: now })));
+        let mut bucket = bucket.lock().unwrap();
+
+        let elapsed = now.saturating_duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * limit.refill_per_sec).min(capacity);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64((1.0 - bucket.tokens) / limit.refill_per_sec))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: BucketConfig = BucketConfig { capacity: 2, refill_per_sec: 1.0 };
+
+    // Teste 1: A rajada é limitada pela capacidade e a recarga libera novas requisições.
+    #[test]
+    fn test_burst_then_refill() {
+        let limiter = RateLimiter::new(&RateLimitConfig::default());
+        let key = RateKey::Subject("conta42".to_string());
+        let start = Instant::now();
+
+        assert!(limiter.check_at(key.clone(), &LIMIT, start).is_ok());
+        assert!(limiter.check_at(key.clone(), &LIMIT, start).is_ok());
+        let wait = limiter.check_at(key.clone(), &LIMIT, start).expect_err("Rajada acima da capacidade");
+        assert!(wait > Duration::ZERO && wait <= Duration::from_secs(1));
+
+        assert!(limiter.check_at(key, &LIMIT, start + Duration::from_secs(1)).is_ok(), "Uma ficha reposta após 1s.");
+    }
+
+    // Teste 2: Chaves distintas (IP e subject) têm baldes independentes.
+    #[test]
+    fn test_keys_are_independent() {
+        let limiter = RateLimiter::new(&RateLimitConfig::default());
+        let limit = BucketConfig { capacity: 1, refill_per_sec: 1.0 };
+        let now = Instant::now();
+        let ip = RateKey::Ip("10.0.0.1".parse().unwrap());
+
+        assert!(limiter.check_at(ip.clone(), &limit, now).is_ok());
+        assert!(limiter.check_at(ip, &limit, now).is_err());
+        assert!(limiter.check_at(RateKey::Subject("conta42".to_string()), &limit, now).is_ok());
+    }
+
+    // Teste 3: Faixas por classe de token; classe desconhecida usa o limite padrão.
+    #[test]
+    fn test_tiers() {
+        let config: RateLimitConfig = serde_yaml::from_str(
+            "per_token: { capacity: 5, refill_per_sec: 1 }\ntiers:\n  gold: { capacity: 100, refill_per_sec: 50 }\n",
+        ).unwrap();
+        assert_eq!(config.for_tier(Some("gold")).capacity, 100);
+        assert_eq!(config.for_tier(Some("bronze")).capacity, 5);
+        assert_eq!(config.for_tier(None).capacity, 5);
+        assert!(config.validate().is_ok());
+
+        let invalid = RateLimitConfig { per_ip: BucketConfig { capacity: 0, refill_per_sec: 1.0 }, ..RateLimitConfig::default() };
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -0,0 +1,131 @@
+// sygma_proxy/src/replay.rs - Proteção contra Replay (nonce + timestamp do token)
+//
+// O cliente gera um token por requisição com `;nonce=<aleatório>;ts=<segundos Unix>` antes
+// de `;sig=`, então os dois ficam cobertos pela assinatura (auth.hmac_key). Em tokens sem
+// assinatura o Proxy ignora nonce e ts: o cliente poderia trocá-los a cada reenvio.
+// O Proxy recusa timestamps fora de `max_skew_secs` e nonces já vistos dentro dessa janela:
+// a mesma linha `token|payload` reenviada não passa mais, mesmo com o token no TRUST_CACHE.
+//
+// Os nonces ficam num cache limitado (`max_tracked_nonces`). Cada um só precisa ser lembrado
+// enquanto o `ts` dele é aceito (no máximo 2 × max_skew_secs depois da primeira vez).
+
+use std::time::Duration;
+
+use moka::sync::Cache;
+use serde::Deserialize;
+use sygma_protocol::SygmaError;
+
+use crate::token::TokenClaims;
+
+// Nonces maiores que isso são recusados (o cache guarda a string inteira)
+const MAX_NONCE_LEN: usize = 128;
+
+// --- SEÇÃO `replay` DO config.yaml ---
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ReplayConfig {
+    // true: todo token precisa de nonce e ts assinados (exige auth.hmac_key).
+    // false: só os tokens assinados que os trazem são verificados
+    pub require_nonce: bool,
+    // Diferença máxima aceita entre o `ts` do token e o relógio do Proxy
+    pub max_skew_secs: u64,
+    pub max_tracked_nonces: u64,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        ReplayConfig { require_nonce: true, max_skew_secs: 300, max_tracked_nonces: 100_000 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    // Sem nonce/ts (com require_nonce) ou nonce grande demais
+    Missing,
+    // `ts` fora da janela de max_skew_secs
+    Stale,
+    // Nonce já usado
+    Replayed,
+}
+
+impl Rejection {
+    pub fn error(self) -> SygmaError {
+        match self {
+            Rejection::Missing => SygmaError::NonceRequired,
+            Rejection::Stale => SygmaError::StaleTimestamp,
+            Rejection::Replayed => SygmaError::ReplayDetected,
+        }
+    }
+}
+
+pub struct ReplayGuard {
+    require_nonce: bool,
+    max_skew_secs: u64,
+    // (subject, nonce): nonces de um subject não colidem com os de outro
+    seen: Cache<(String, String), ()>,
+}
+
+impl ReplayGuard {
+    pub fn new(config: &ReplayConfig) -> Self {
+        ReplayGuard {
+            require_nonce: config.require_nonce,
+            max_skew_secs: config.max_skew_secs,
+            seen: Cache::builder()
+                .max_capacity(config.max_tracked_nonces)
+                .time_to_live(Duration::from_secs(config.max_skew_secs.saturating_mul(2).max(1)))
+                .build(),
+        }
+    }
+
+    // Verifica e consome o nonce: a segunda chamada com os mesmos claims é um replay
+    pub fn check(&self, claims: &TokenClaims, now_unix: u64) -> Result<(), Rejection> {
+        let (nonce, ts) = match (&claims.nonce, claims.ts) {
+            (Some(nonce), Some(ts)) if nonce.len() <= MAX_NONCE_LEN => (nonce, ts),
+            (None, None) if !self.require_nonce => return Ok(()),
+            _ => return Err(Rejection::Missing),
+        };
+        if now_unix.abs_diff(ts) > self.max_skew_secs {
+            return Err(Rejection::Stale);
+        }
+        let entry = self.seen.entry((claims.subject.clone(), nonce.clone())).or_insert(());
+        if entry.is_fresh() {
+            Ok(())
+        } else {
+            Err(Rejection::Replayed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(nonce: Option<&str>, ts: Option<u64>) -> TokenClaims {
+        TokenClaims { subject: "conta42".to_string(), exp: None, tier: None, scopes: Vec::new(), nonce: nonce.map(str::to_string), ts }
+    }
+
+    // Teste 1: Um nonce só passa uma vez, e só com o timestamp dentro da janela.
+    #[test]
+    fn test_nonce_and_skew() {
+        let guard = ReplayGuard::new(&ReplayConfig { max_skew_secs: 60, ..ReplayConfig::default() });
+        let now = 1_700_000_000;
+
+        assert_eq!(guard.check(&claims(Some("n1"), Some(now - 30)), now), Ok(()));
+        assert_eq!(guard.check(&claims(Some("n1"), Some(now - 30)), now), Err(Rejection::Replayed));
+        assert_eq!(guard.check(&claims(Some("n2"), Some(now - 61)), now), Err(Rejection::Stale));
+        assert_eq!(guard.check(&claims(Some("n3"), Some(now + 61)), now), Err(Rejection::Stale));
+        assert_eq!(guard.check(&claims(Some("n4"), None), now), Err(Rejection::Missing));
+
+        let other_subject = TokenClaims { subject: "conta43".to_string(), ..claims(Some("n1"), Some(now)) };
+        assert_eq!(guard.check(&other_subject, now), Ok(()), "Nonces são por subject.");
+    }
+
+    // Teste 2: Tokens sem nonce só passam quando `require_nonce` está desligado (o padrão é exigir).
+    #[test]
+    fn test_require_nonce() {
+        let now = 1_700_000_000;
+        assert_eq!(ReplayGuard::new(&ReplayConfig::default()).check(&claims(None, None), now), Err(Rejection::Missing));
+        let lenient = ReplayGuard::new(&ReplayConfig { require_nonce: false, ..ReplayConfig::default() });
+        assert_eq!(lenient.check(&claims(None, None), now), Ok(()));
+    }
+}
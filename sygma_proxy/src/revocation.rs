@@ -0,0 +1,142 @@
+// sygma_proxy/src/revocation.rs - Lista de Revogação de Tokens (Zero Trust)
+
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use tracing::{error, info};
+
+use crate::token;
+use crate::watch::file_signature;
+use crate::{config, TRUST_CACHE};
+
+// Conjunto de tokens revogados. Tem prioridade sobre o TRUST_CACHE:
+// um token revogado é negado mesmo que pareça válido.
+lazy_static! {
+    static ref REVOKED_TOKENS: RwLock<HashSet<String>> = RwLock::new(HashSet::new());
+}
+
+pub fn is_revoked(token: &str) -> bool {
+    REVOKED_TOKENS.read().unwrap().contains(token)
+}
+
+// Formato do arquivo: um token por linha. Linhas vazias e comentários (#) são ignorados.
+fn parse_revocation_list(contents: &str) -> HashSet<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+// Arquivo inexistente equivale a uma lista vazia.
+pub fn load_revocation_file(path: &Path) -> io::Result<HashSet<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(parse_revocation_list(&contents)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(HashSet::new()),
+        Err(e) => Err(e),
+    }
+}
+
+// Substitui a lista em memória pelo conteúdo do arquivo (fonte da verdade).
+// Tokens recém-revogados são invalidados no cache. Retorna quantos foram adicionados.
+pub fn apply_revocation_list(new_list: HashSet<String>) -> usize {
+    let mut revoked = REVOKED_TOKENS.write().unwrap();
+    let mut added = 0;
+    for token in new_list.difference(&revoked) {
+        TRUST_CACHE.invalidate(&token::cache_key(token));
+        added += 1;
+    }
+    *revoked = new_list;
+    added
+}
+
+// Comando administrativo: acrescenta o token ao arquivo vigiado pelo Proxy.
+pub fn append_to_revocation_file(path: &Path, token: &str) -> io::Result<()> {
+    let token = token.trim();
+    if token.is_empty() || token.contains('\n') {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Token vazio ou inválido"));
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", token)
+}
+
+// Vigia o arquivo de revogação e reaplica a lista sempre que ele muda.
+// O caminho é relido da configuração ativa, então uma recarga do YAML troca o arquivo vigiado.
+pub async fn watch_revocation_file(poll_interval: Duration) {
+    let mut last_seen = config::current().revocation_file.as_deref()
+        .map(|path| (PathBuf::from(path), file_signature(Path::new(path))));
+    let mut ticker = tokio::time::interval(poll_interval);
+
+    loop {
+        ticker.tick().await;
+        let Some(path) = config::current().revocation_file.as_deref().map(PathBuf::from) else {
+            if last_seen.take().is_some() {
+                apply_revocation_list(HashSet::new());
+                info!("revocation_file removido da configuração. Lista esvaziada.");
+            }
+            continue;
+        };
+
+        let signature = file_signature(&path);
+        if last_seen.as_ref() == Some(&(path.clone(), signature)) {
+            continue;
+        }
+        last_seen = Some((path.clone(), signature));
+
+        match load_revocation_file(&path) {
+            Ok(list) => {
+                let total = list.len();
+                let added = apply_revocation_list(list);
+                info!("Lista recarregada de {} ({} tokens, {} novos).", path.display(), total, added);
+            }
+            Err(e) => error!("Falha ao ler {}: {}", path.display(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verify_zero_trust_token;
+
+    // A lista de revogação é global: os testes que a alteram rodam em série.
+    static LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    // Teste 1: Revogar remove o token do cache e nega as verificações seguintes.
+    #[tokio::test]
+    async fn test_revoke_invalidates_cached_token() {
+        let _guard = LOCK.lock().await;
+        let token = "AUTH_SYGMA_VALID_REVOKE_TEST";
+        assert!(verify_zero_trust_token(&token::request_token(token)).await);
+
+        apply_revocation_list(HashSet::from([token.to_string()]));
+
+        assert!(TRUST_CACHE.get(token).is_none(), "A revogação deve invalidar o cache imediatamente.");
+        assert!(!verify_zero_trust_token(&token::request_token(token)).await, "Token revogado deve falhar mesmo parecendo válido.");
+    }
+
+    // Teste 2: O arquivo é a fonte da verdade (adicionar e remover linhas).
+    #[tokio::test]
+    async fn test_revocation_file_roundtrip() {
+        let _guard = LOCK.lock().await;
+        let path = std::env::temp_dir().join(format!("sygma_revoked_{}.txt", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let token = "AUTH_SYGMA_VALID_FILE_TEST";
+
+        append_to_revocation_file(&path, token).unwrap();
+        let list = load_revocation_file(&path).unwrap();
+        apply_revocation_list(list);
+        assert!(is_revoked(token));
+
+        std::fs::write(&path, "# lista vazia\n").unwrap();
+        apply_revocation_list(load_revocation_file(&path).unwrap());
+        assert!(!is_revoked(token));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+// sygma_proxy/src/shutdown.rs - Encerramento Gracioso (SIGTERM / SIGINT)
+//
+// Ao receber o sinal, os listeners TCP e HTTP param de aceitar conexões e o Proxy espera as
+// requisições em andamento terminarem, até `shutdown.drain_timeout_secs`. Depois disso os logs
+// são descarregados e o processo sai; o que ainda estiver em andamento é abandonado.
+
+use std::io::{self, Write};
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+
+// --- SEÇÃO `shutdown` DO config.yaml ---
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ShutdownConfig {
+    // Prazo para as requisições em andamento terminarem (lido no momento do sinal)
+    pub drain_timeout_secs: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        ShutdownConfig { drain_timeout_secs: 30 }
+    }
+}
+
+// Estado do encerramento: sinal de parada + contagem de requisições em andamento
+pub struct Drain {
+    stopping: watch::Sender<bool>,
+    active: watch::Sender<usize>,
+}
+
+// Enquanto existir, a requisição conta como em andamento
+pub struct InFlight<'a>(&'a Drain);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.active.send_modify(|active| *active -= 1);
+    }
+}
+
+impl Drain {
+    pub fn new() -> Self {
+        Drain { stopping: watch::Sender::new(false), active: watch::Sender::new(0) }
+    }
+
+    pub fn begin(&self) -> InFlight<'_> {
+        self.active.send_modify(|active| *active += 1);
+        InFlight(self)
+    }
+
+    pub fn active(&self) -> usize {
+        *self.active.borrow()
+    }
+
+    pub fn stop(&self) {
+        self.stopping.send_replace(true);
+    }
+
+    pub fn is_stopping(&self) -> bool {
+        *self.stopping.borrow()
+    }
+
+    // Completa quando o encerramento começa (imediatamente, se já começou)
+    pub async fn stopped(&self) {
+        let _ = self.stopping.subscribe().wait_for(|stopping| *stopping).await;
+    }
+
+    // `false` se o prazo acabou com requisições ainda em andamento
+    pub async fn wait_idle(&self, timeout: Duration) -> bool {
+        let mut active = self.active.subscribe();
+        let drained = tokio::time::timeout(timeout, active.wait_for(|active| *active == 0)).await;
+        drained.is_ok()
+    }
+}
+
+impl Default for Drain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+lazy_static! {
+    pub static ref DRAIN: Drain = Drain::new();
+}
+
+// Completa no primeiro SIGTERM ou SIGINT
+pub async fn wait_for_signal() -> io::Result<&'static str> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    Ok(tokio::select! {
+        _ = terminate.recv() => "SIGTERM",
+        _ = interrupt.recv() => "SIGINT",
+    })
+}
+
+// Último passo antes de sair: nada do que foi logado pode se perder no buffer
+pub fn flush() {
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Teste 1: A drenagem espera as requisições em andamento e respeita o prazo.
+    #[tokio::test]
+    async fn test_drain_waits_for_in_flight() {
+        let drain = Drain::new();
+        let in_flight = drain.begin();
+        drain.stop();
+        drain.stopped().await;
+
+        assert!(!drain.wait_idle(Duration::from_millis(20)).await, "Requisição em andamento segura a drenagem.");
+        assert_eq!(drain.active(), 1);
+        drop(in_flight);
+        assert!(drain.wait_idle(Duration::from_millis(20)).await);
+    }
+}
@@ -0,0 +1,419 @@
+// sygma_proxy/src/store_forward.rs - Fila Durável (store-and-forward) com o Kernel Fora do Ar
+//
+// Com a seção `store_and_forward`, um Settlement autenticado, validado e aprovado pela política
+// que encontraria o Kernel indisponível (503) vai para uma fila local e o cliente recebe um
+// ticket: "202 ACCEPTED: ... (Ticket: tkt_...)". Uma task entrega a fila em ordem (FIFO) assim
+// que o Kernel volta; enquanto houver itens na fila, Settlements novos entram no fim dela.
+// Consulta do ticket: `token|TICKET <ticket>` no TCP; GET /v1/settlements/{id} no HTTP.
+//
+// Durabilidade: cada entrada e cada resultado são acrescentados ao arquivo JSONL (`file`) com
+// fsync antes da resposta. Na inicialização o arquivo é relido (a fila sobrevive a um reinício)
+// e compactado; resultados mais antigos que `ticket_retention_secs` são descartados.
+//
+// Cada tentativa de entrega também fica no arquivo antes do envio. Um ticket que estava sendo
+// entregue numa queda pode ter chegado ao Kernel: na inicialização ele termina como o 502 de
+// resultado incerto, nunca reenviado.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sygma_protocol::SygmaError;
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+// --- SEÇÃO `store_and_forward` DO config.yaml ---
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct StoreForwardConfig {
+    // Arquivo JSONL da fila (entradas e resultados)
+    pub file: String,
+    // Acima disso, o Settlement volta a receber 503
+    pub max_pending: usize,
+    // Intervalo entre tentativas de entrega com o Kernel fora do ar
+    pub retry_interval_ms: u64,
+    // Por quanto tempo o resultado de um ticket entregue pode ser consultado
+    pub ticket_retention_secs: u64,
+}
+
+impl Default for StoreForwardConfig {
+    fn default() -> Self {
+        StoreForwardConfig {
+            file: "queue.jsonl".to_string(),
+            max_pending: 10_000,
+            retry_interval_ms: 2_000,
+            ticket_retention_secs: 7 * 86_400,
+        }
+    }
+}
+
+impl StoreForwardConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.file.is_empty() {
+            return Err("store_and_forward.file está vazio".to_string());
+        }
+        if self.max_pending == 0 || self.retry_interval_ms == 0 || self.ticket_retention_secs == 0 {
+            return Err("store_and_forward.max_pending, retry_interval_ms e ticket_retention_secs devem ser maiores que zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+// Linha do arquivo da fila
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum LogRecord {
+    Queued(Queued),
+    // Entrega em andamento: o Settlement pode ter chegado ao Kernel
+    Delivering { ticket: String },
+    // A tentativa terminou sem chegar ao Kernel (503): o ticket volta a ser só pendente
+    Requeued { ticket: String },
+    Finished { ticket: String, subject: String, response: String, finished_at: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Queued {
+    pub ticket: String,
+    pub subject: String,
+    // Forma canônica (já validada)
+    pub payload: String,
+    // ID de correlação da requisição que criou o ticket
+    pub cid: String,
+    pub queued_at: u64,
+    // Valor reservado no total diário da conta de origem (política), recontado num reinício
+    #[serde(default)]
+    pub reserved: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TicketStatus {
+    // Posição na fila, começando em 1
+    Queued(usize),
+    // Linha de resposta do Kernel (200, 422 ou 502)
+    Finished(String),
+}
+
+impl TicketStatus {
+    // Estado do ticket no formato dos Settlements da API HTTP
+    pub fn label(&self) -> &'static str {
+        match self {
+            TicketStatus::Queued(_) => "queued",
+            TicketStatus::Finished(response) => match response.get(..3) {
+                Some("200") => "settled",
+                Some("422") => "rejected",
+                _ => "failed",
+            },
+        }
+    }
+}
+
+struct Finished {
+    subject: String,
+    response: String,
+    finished_at: u64,
+}
+
+struct State {
+    pending: VecDeque<Queued>,
+    finished: HashMap<String, Finished>,
+    file: File,
+    next_sequence: u64,
+}
+
+pub struct StoreForward {
+    config: StoreForwardConfig,
+    path: PathBuf,
+    state: Mutex<State>,
+    // Acorda a task de entrega a cada novo item
+    wakeup: Notify,
+}
+
+#[derive(Debug)]
+pub enum EnqueueError {
+    Full,
+    Io(io::Error),
+}
+
+fn now_unix() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+// Sequência no fim do ticket (`tkt_<ms><seq>`), para continuar a numeração após um reinício
+fn ticket_sequence(ticket: &str) -> Option<u64> {
+    let start = ticket.len().checked_sub(4)?;
+    u64::from_str_radix(ticket.get(start..)?, 16).ok()
+}
+
+fn append(file: &mut File, record: &LogRecord) -> io::Result<()> {
+    let mut line = serde_json::to_vec(record).map_err(io::Error::other)?;
+    line.push(b'\n');
+    file.write_all(&line)?;
+    file.sync_data()
+}
+
+impl StoreForward {
+    // Relê o arquivo (se existir), descarta resultados expirados e reescreve só o estado vivo
+    pub fn open(config: &StoreForwardConfig) -> io::Result<Self> {
+        let path = PathBuf::from(&config.file);
+        let mut pending: VecDeque<Queued> = VecDeque::new();
+        let mut finished = HashMap::new();
+        let mut delivering = HashSet::new();
+
+        if let Ok(file) = File::open(&path) {
+            for (number, line) in BufReader::new(file).lines().enumerate() {
+                let line = line?;
+                match serde_json::from_str::<LogRecord>(&line) {
+                    Ok(LogRecord::Queued(queued)) => pending.push_back(queued),
+                    Ok(LogRecord::Delivering { ticket }) => {
+                        delivering.insert(ticket);
+                    }
+                    Ok(LogRecord::Requeued { ticket }) => {
+                        delivering.remove(&ticket);
+                    }
+                    Ok(LogRecord::Finished { ticket, subject, response, finished_at }) => {
+                        delivering.remove(&ticket);
+                        pending.retain(|queued| queued.ticket != ticket);
+                        finished.insert(ticket, Finished { subject, response, finished_at });
+                    }
+                    // Última linha cortada por uma queda no meio da escrita
+                    Err(e) => warn!("Linha {} de {} ignorada: {}", number + 1, path.display(), e),
+                }
+            }
+        }
+
+        // Entrega interrompida: o resultado é incerto e o Settlement não é reenviado
+        for queued in pending.iter().filter(|queued| delivering.contains(&queued.ticket)) {
+            warn!("Ticket {} estava sendo entregue ao Kernel numa queda. Resultado incerto, sem reenvio.", queued.ticket);
+            let response = SygmaError::KernelFailed.response();
+            finished.insert(queued.ticket.clone(), Finished { subject: queued.subject.clone(), response, finished_at: now_unix() });
+        }
+        pending.retain(|queued| !delivering.contains(&queued.ticket));
+
+        let cutoff = now_unix().saturating_sub(config.ticket_retention_secs);
+        finished.retain(|_, result: &mut Finished| result.finished_at >= cutoff);
+        let next_sequence = pending.iter().map(|queued| queued.ticket.as_str())
+            .chain(finished.keys().map(String::as_str))
+            .filter_map(ticket_sequence)
+            .max()
+            .map_or(0, |sequence| sequence + 1);
+        let file = compact(&path, &pending, &finished)?;
+        if !pending.is_empty() {
+            info!("{} Settlement(s) pendente(s) recuperado(s) de {}.", pending.len(), path.display());
+        }
+
+        Ok(StoreForward {
+            config: config.clone(),
+            path,
+            state: Mutex::new(State { pending, finished, file, next_sequence }),
+            wakeup: Notify::new(),
+        })
+    }
+
+    pub fn pending(&self) -> usize {
+        self.state.lock().unwrap().pending.len()
+    }
+
+    pub fn retry_interval(&self) -> Duration {
+        Duration::from_millis(self.config.retry_interval_ms)
+    }
+
+    // Grava o Settlement no disco (fsync) e devolve o ticket
+    pub fn enqueue(&self, subject: &str, payload: &str, cid: &str, reserved: u64) -> Result<String, EnqueueError> {
+        let mut state = self.state.lock().unwrap();
+        if state.pending.len() >= self.config.max_pending {
+            return Err(EnqueueError::Full);
+        }
+        let queued_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let sequence = state.next_sequence;
+        let queued = Queued {
+            ticket: format!("tkt_{:x}{:04x}", queued_at.as_millis(), sequence & 0xffff),
+            subject: subject.to_string(),
+            payload: payload.to_string(),
+            cid: cid.to_string(),
+            queued_at: queued_at.as_secs(),
+            reserved,
+        };
+        append(&mut state.file, &LogRecord::Queued(queued.clone())).map_err(EnqueueError::Io)?;
+        state.next_sequence += 1;
+        state.pending.push_back(queued.clone());
+        drop(state);
+        self.wakeup.notify_one();
+        Ok(queued.ticket)
+    }
+
+    // Itens ainda não entregues, em ordem
+    pub fn pending_items(&self) -> Vec<Queued> {
+        self.state.lock().unwrap().pending.iter().cloned().collect()
+    }
+
+    // Próximo item a entregar (continua na fila até `finish`)
+    pub fn head(&self) -> Option<Queued> {
+        self.state.lock().unwrap().pending.front().cloned()
+    }
+
+    // Registra (fsync) a tentativa de entrega antes do envio ao Kernel
+    pub fn begin_delivery(&self, ticket: &str) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        append(&mut state.file, &LogRecord::Delivering { ticket: ticket.to_string() })
+    }
+
+    // A tentativa não chegou ao Kernel (503): o ticket continua na fila para a próxima passada
+    pub fn requeue(&self, ticket: &str) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        append(&mut state.file, &LogRecord::Requeued { ticket: ticket.to_string() })
+    }
+
+    // Resultado da entrega do primeiro item. Com a fila vazia, o arquivo é compactado.
+    pub fn finish(&self, ticket: &str, response: &str) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let Some(index) = state.pending.iter().position(|queued| queued.ticket == ticket) else {
+            return Ok(());
+        };
+        let queued = state.pending.remove(index).unwrap();
+        let finished_at = now_unix();
+        let record = LogRecord::Finished { ticket: ticket.to_string(), subject: queued.subject.clone(), response: response.to_string(), finished_at };
+        let written = append(&mut state.file, &record);
+        state.finished.insert(ticket.to_string(), Finished { subject: queued.subject, response: response.to_string(), finished_at });
+        written?;
+
+        if state.pending.is_empty() {
+            let cutoff = finished_at.saturating_sub(self.config.ticket_retention_secs);
+            state.finished.retain(|_, result| result.finished_at >= cutoff);
+            state.file = compact(&self.path, &state.pending, &state.finished)?;
+        }
+        Ok(())
+    }
+
+    // Tickets de outro subject são indistinguíveis de inexistentes
+    pub fn status(&self, subject: &str, ticket: &str) -> Option<TicketStatus> {
+        let state = self.state.lock().unwrap();
+        if let Some(position) = state.pending.iter().position(|queued| queued.ticket == ticket) {
+            return (state.pending[position].subject == subject).then_some(TicketStatus::Queued(position + 1));
+        }
+        state.finished.get(ticket)
+            .filter(|result| result.subject == subject)
+            .map(|result| TicketStatus::Finished(result.response.clone()))
+    }
+
+    // Espera um item novo ou o intervalo de nova tentativa
+    pub async fn wait(&self) {
+        let _ = tokio::time::timeout(self.retry_interval(), self.wakeup.notified()).await;
+    }
+}
+
+// Reescreve o arquivo com o estado vivo (arquivo temporário + rename) e o reabre para acréscimo
+fn compact(path: &Path, pending: &VecDeque<Queued>, finished: &HashMap<String, Finished>) -> io::Result<File> {
+    let temporary = path.with_extension("jsonl.tmp");
+    let mut file = File::create(&temporary)?;
+    let mut results: Vec<_> = finished.iter().collect();
+    results.sort_by_key(|(_, result)| result.finished_at);
+    for (ticket, result) in results {
+        let record = LogRecord::Finished { ticket: ticket.clone(), subject: result.subject.clone(), response: result.response.clone(), finished_at: result.finished_at };
+        append(&mut file, &record)?;
+    }
+    for queued in pending {
+        append(&mut file, &LogRecord::Queued(queued.clone()))?;
+    }
+    std::fs::rename(&temporary, path)?;
+    OpenOptions::new().append(true).open(path)
+}
+
+static QUEUE: OnceLock<StoreForward> = OnceLock::new();
+
+// Abre a fila na inicialização; sem a seção `store_and_forward`, não faz nada
+pub fn init(config: Option<&StoreForwardConfig>) -> io::Result<()> {
+    if let Some(config) = config {
+        let _ = QUEUE.set(StoreForward::open(config)?);
+        info!("Fila store-and-forward ativa em {}.", config.file);
+    }
+    Ok(())
+}
+
+pub fn queue() -> Option<&'static StoreForward> {
+    QUEUE.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(name: &str) -> StoreForwardConfig {
+        let file = std::env::temp_dir().join(format!("sygma_queue_{}_{}.jsonl", name, std::process::id()));
+        let _ = std::fs::remove_file(&file);
+        StoreForwardConfig { file: file.display().to_string(), max_pending: 2, ..StoreForwardConfig::default() }
+    }
+
+    // Teste 1: Ordem FIFO, limite da fila e estado do ticket visível só para o seu subject.
+    #[test]
+    fn test_fifo_and_ticket_status() {
+        let config = config("fifo");
+        let queue = StoreForward::open(&config).unwrap();
+        let first = queue.enqueue("conta42", "ZKP_HASH_S:1_R:2_A:3", "cid1", 0).unwrap();
+        let second = queue.enqueue("conta42", "ZKP_HASH_S:1_R:2_A:4", "cid2", 0).unwrap();
+        assert_ne!(first, second);
+        assert!(matches!(queue.enqueue("conta42", "ZKP_HASH_S:1_R:2_A:5", "cid3", 0), Err(EnqueueError::Full)));
+
+        assert_eq!(queue.head().unwrap().ticket, first);
+        assert_eq!(queue.status("conta42", &second), Some(TicketStatus::Queued(2)));
+        assert_eq!(queue.status("conta43", &second), None, "Ticket de outro subject.");
+
+        queue.finish(&first, "200 OK: Payload liquidado").unwrap();
+        assert_eq!(queue.head().unwrap().ticket, second);
+        let status = queue.status("conta42", &first).unwrap();
+        assert_eq!((status.label(), queue.status("conta42", &second).unwrap().label()), ("settled", "queued"));
+        std::fs::remove_file(&config.file).unwrap();
+    }
+
+    // Teste 2: A fila e os resultados sobrevivem a um reinício; a linha cortada é ignorada.
+    #[test]
+    fn test_queue_survives_restart() {
+        let config = config("restart");
+        let queue = StoreForward::open(&config).unwrap();
+        let settled = queue.enqueue("conta42", "ZKP_HASH_S:1_R:2_A:3", "cid1", 0).unwrap();
+        let pending = queue.enqueue("conta42", "ZKP_HASH_S:1_R:2_A:4", "cid2", 4).unwrap();
+        queue.finish(&settled, "422 SETTLEMENT REJECTED: Regra de Ouro violada").unwrap();
+        drop(queue);
+        let mut file = OpenOptions::new().append(true).open(&config.file).unwrap();
+        file.write_all(b"{\"op\":\"queued\",\"tick").unwrap();
+
+        let reopened = StoreForward::open(&config).unwrap();
+        assert_eq!(reopened.pending(), 1);
+        assert_eq!(reopened.head().unwrap().ticket, pending);
+        assert_eq!(reopened.pending_items()[0].reserved, 4, "A reserva da política é recuperada com o ticket.");
+        assert_eq!(reopened.status("conta42", &settled).unwrap().label(), "rejected");
+
+        reopened.finish(&pending, "200 OK: Payload liquidado").unwrap();
+        let contents = std::fs::read_to_string(&config.file).unwrap();
+        assert_eq!(contents.lines().count(), 2, "Com a fila vazia, só os resultados ficam no arquivo.");
+        std::fs::remove_file(&config.file).unwrap();
+    }
+
+    // Teste 3: Entrega interrompida por uma queda vira 502 incerto; a que não chegou ao Kernel continua na fila.
+    #[test]
+    fn test_interrupted_delivery_not_resent() {
+        let config = config("delivering");
+        let queue = StoreForward::open(&config).unwrap();
+        let interrupted = queue.enqueue("conta42", "ZKP_HASH_S:1_R:2_A:3", "cid1", 0).unwrap();
+        let requeued = queue.enqueue("conta42", "ZKP_HASH_S:1_R:2_A:4", "cid2", 0).unwrap();
+        queue.begin_delivery(&requeued).unwrap();
+        queue.requeue(&requeued).unwrap();
+        queue.begin_delivery(&interrupted).unwrap();
+        drop(queue);
+
+        let reopened = StoreForward::open(&config).unwrap();
+        assert_eq!(reopened.pending(), 1);
+        assert_eq!(reopened.head().unwrap().ticket, requeued);
+        let status = reopened.status("conta42", &interrupted).unwrap();
+        assert_eq!(status, TicketStatus::Finished(SygmaError::KernelFailed.response()));
+        assert_eq!(status.label(), "failed");
+
+        // A numeração continua depois dos tickets recuperados
+        let next = reopened.enqueue("conta42", "ZKP_HASH_S:1_R:2_A:5", "cid3", 0).unwrap();
+        assert_eq!(ticket_sequence(&next), ticket_sequence(&requeued).map(|sequence| sequence + 1));
+        std::fs::remove_file(&config.file).unwrap();
+    }
+}
@@ -0,0 +1,575 @@
+// sygma_proxy/src/tls.rs - Terminação TLS no Listener (rustls) e mTLS
+//
+// Com a seção `tls` no config.yaml, o Proxy só aceita conexões TLS. O certificado e a
+// chave são vigiados: ao mudarem (renovação), novas conexões usam o par novo sem reinício.
+// Um par inválido é rejeitado e o anterior continua em uso.
+//
+// Com `tls.client_auth`, o certificado do cliente é pedido no handshake e validado logo
+// depois dele, contra o CA configurado. Assim o Proxy responde com o motivo exato
+// (ausente, expirado, CA desconhecido) em vez de um alerta TLS genérico.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use serde::Deserialize;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::rustls::client::danger::HandshakeSignatureValid;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, UnixTime};
+use tokio_rustls::rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{CertificateError, DigitallySignedStruct, DistinguishedName, RootCertStore, ServerConfig, SignatureScheme};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tracing::{error, info};
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::config;
+use crate::metrics::METRICS;
+use crate::watch::file_signature;
+
+// Protocolo anunciado via ALPN (o sygma_client oferece o mesmo)
+pub const SYGMA_ALPN: &str = "sygma/1";
+
+// ALPN da API HTTP: curl, navegadores e bibliotecas HTTP oferecem http/1.1, e o rustls recusa
+// o handshake quando não há protocolo em comum
+const HTTP_ALPN: &str = "http/1.1";
+
+// --- SEÇÃO `tls` DO config.yaml ---
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TlsConfig {
+    // Cadeia de certificados e chave privada em PEM
+    pub cert_file: String,
+    pub key_file: String,
+    #[serde(default = "default_alpn")]
+    pub alpn_protocols: Vec<String>,
+    // mTLS: exige certificado de cliente assinado pelo CA configurado
+    #[serde(default)]
+    pub client_auth: Option<ClientAuthConfig>,
+}
+
+fn default_alpn() -> Vec<String> {
+    vec![SYGMA_ALPN.to_string()]
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ClientAuthConfig {
+    // CA (PEM) que assina os certificados dos dispositivos
+    pub ca_file: String,
+    #[serde(default)]
+    pub identity_from: IdentitySource,
+    #[serde(default)]
+    pub token_mode: TokenMode,
+}
+
+// De onde vem a identidade do cliente no certificado
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdentitySource {
+    // Common Name do subject
+    #[default]
+    CommonName,
+    // Primeiro SAN do tipo DNS, URI ou e-mail
+    San,
+}
+
+// Como a identidade do certificado convive com o token
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenMode {
+    // Certificado E token válidos
+    #[default]
+    Alongside,
+    // O certificado substitui o token (o campo do token é ignorado)
+    Instead,
+}
+
+// Motivos distintos de rejeição do certificado de cliente
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientCertError {
+    Missing,
+    Expired,
+    UnknownCa,
+    Invalid(String),
+}
+
+impl fmt::Display for ClientCertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientCertError::Missing => f.write_str("Client Certificate Missing"),
+            ClientCertError::Expired => f.write_str("Client Certificate Expired"),
+            ClientCertError::UnknownCa => f.write_str("Client Certificate Unknown CA"),
+            ClientCertError::Invalid(reason) => write!(f, "Client Certificate Invalid ({})", reason),
+        }
+    }
+}
+
+// Resultado da autenticação do cliente: `None` quando o mTLS não está configurado
+pub type ClientIdentity = Option<Result<String, ClientCertError>>;
+
+// Pede o certificado no handshake sem decidir nada; a validação da cadeia fica para
+// `ClientAuth::identify`, que devolve o motivo da rejeição para a aplicação.
+// As assinaturas do handshake continuam verificadas (o cliente prova a posse da chave).
+#[derive(Debug)]
+struct DeferredClientVerifier {
+    inner: Arc<dyn ClientCertVerifier>,
+}
+
+impl ClientCertVerifier for DeferredClientVerifier {
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        self.inner.root_hint_subjects()
+    }
+
+    fn client_auth_mandatory(&self) -> bool {
+        false
+    }
+
+    fn verify_client_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _now: UnixTime,
+    ) -> Result<ClientCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+struct ClientAuth {
+    verifier: Arc<dyn ClientCertVerifier>,
+    identity_from: IdentitySource,
+}
+
+impl ClientAuth {
+    fn new(config: &ClientAuthConfig) -> io::Result<Self> {
+        let mut roots = RootCertStore::empty();
+        for cert in load_certs(&config.ca_file)? {
+            roots.add(cert).map_err(|e| invalid(format!("CA de clientes inválido em {}: {}", config.ca_file, e)))?;
+        }
+        let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), Arc::new(ring::default_provider()))
+            .build()
+            .map_err(|e| invalid(format!("CA de clientes inválido: {}", e)))?;
+        Ok(ClientAuth { verifier, identity_from: config.identity_from })
+    }
+
+    // Valida a cadeia apresentada e extrai a identidade
+    fn identify(&self, chain: Option<&[CertificateDer<'static>]>, now: UnixTime) -> Result<String, ClientCertError> {
+        let Some((end_entity, intermediates)) = chain.and_then(|chain| chain.split_first()) else {
+            return Err(ClientCertError::Missing);
+        };
+
+        self.verifier.verify_client_cert(end_entity, intermediates, now).map_err(|e| match e {
+            tokio_rustls::rustls::Error::InvalidCertificate(
+                CertificateError::Expired | CertificateError::ExpiredContext { .. },
+            ) => ClientCertError::Expired,
+            // Emissor fora do CA configurado, ou que só imita o nome dele (assinatura não confere)
+            tokio_rustls::rustls::Error::InvalidCertificate(
+                CertificateError::UnknownIssuer | CertificateError::BadSignature,
+            ) => ClientCertError::UnknownCa,
+            other => ClientCertError::Invalid(other.to_string()),
+        })?;
+
+        certificate_identity(end_entity, self.identity_from)
+            .ok_or_else(|| ClientCertError::Invalid("sem identidade no certificado".to_string()))
+    }
+}
+
+// Identidade do certificado: Common Name do subject ou primeiro SAN (DNS, URI ou e-mail)
+fn certificate_identity(der: &CertificateDer<'_>, source: IdentitySource) -> Option<String> {
+    let (_, cert) = X509Certificate::from_der(der.as_ref()).ok()?;
+    match source {
+        IdentitySource::CommonName => cert.subject().iter_common_name().next()?.as_str().ok().map(str::to_string),
+        IdentitySource::San => cert.subject_alternative_name().ok()??.value.general_names.iter()
+            .find_map(|name| match name {
+                GeneralName::DNSName(value) | GeneralName::URI(value) | GeneralName::RFC822Name(value) => Some(value.to_string()),
+                _ => None,
+            }),
+    }
+}
+
+// Acceptor TLS em uso, com a validação de clientes (mTLS) quando configurada
+pub struct TlsListener {
+    acceptor: TlsAcceptor,
+    client_auth: Option<ClientAuth>,
+}
+
+impl TlsListener {
+    // Handshake + identificação do cliente. A rejeição do certificado não encerra a conexão
+    // aqui: o chamador responde com o motivo.
+    pub async fn accept<S>(&self, stream: S, timeout: Duration) -> io::Result<(TlsStream<S>, ClientIdentity)>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let tls_stream = tokio::time::timeout(timeout, self.acceptor.accept(stream)).await
+            .map_err(|_| {
+                METRICS.record_connection_timeout("handshake");
+                io::Error::new(io::ErrorKind::TimedOut, "Timeout do handshake TLS")
+            })??;
+        let identity = self.client_auth.as_ref().map(|client_auth| {
+            client_auth.identify(tls_stream.get_ref().1.peer_certificates(), UnixTime::now())
+        });
+        Ok((tls_stream, identity))
+    }
+}
+
+// Listeners em uso (TCP e API HTTP); trocados atomicamente quando certificados são recarregados
+lazy_static! {
+    static ref LISTENER: RwLock<Option<Arc<TlsListener>>> = RwLock::new(None);
+    static ref HTTP_LISTENER: RwLock<Option<Arc<TlsListener>>> = RwLock::new(None);
+}
+
+pub fn listener() -> Option<Arc<TlsListener>> {
+    LISTENER.read().unwrap().clone()
+}
+
+pub fn http_listener() -> Option<Arc<TlsListener>> {
+    HTTP_LISTENER.read().unwrap().clone()
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn open(path: &str) -> io::Result<BufReader<File>> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| io::Error::new(e.kind(), format!("Falha ao ler {}: {}", path, e)))
+}
+
+pub fn load_certs(path: &str) -> io::Result<Vec<CertificateDer<'static>>> {
+    let certs = rustls_pemfile::certs(&mut open(path)?).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(invalid(format!("Nenhum certificado PEM em {}", path)));
+    }
+    Ok(certs)
+}
+
+fn load_private_key(path: &str) -> io::Result<PrivateKeyDer<'static>> {
+    rustls_pemfile::private_key(&mut open(path)?)?
+        .ok_or_else(|| invalid(format!("Nenhuma chave privada PEM em {}", path)))
+}
+
+// Monta o listener do protocolo TCP (também valida que a chave corresponde ao certificado)
+pub fn build_listener(config: &TlsConfig) -> io::Result<TlsListener> {
+    build(config, &config.alpn_protocols)
+}
+
+// Mesmo certificado e mTLS, com o ALPN da API HTTP
+pub fn build_http_listener(config: &TlsConfig) -> io::Result<TlsListener> {
+    build(config, &[HTTP_ALPN.to_string()])
+}
+
+fn build(config: &TlsConfig, alpn_protocols: &[String]) -> io::Result<TlsListener> {
+    let certs = load_certs(&config.cert_file)?;
+    let key = load_private_key(&config.key_file)?;
+    let client_auth = config.client_auth.as_ref().map(ClientAuth::new).transpose()?;
+
+    let builder = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| invalid(e.to_string()))?;
+    let builder = match &client_auth {
+        Some(client_auth) => builder.with_client_cert_verifier(Arc::new(DeferredClientVerifier { inner: client_auth.verifier.clone() })),
+        None => builder.with_no_client_auth(),
+    };
+    let mut server_config = builder
+        .with_single_cert(certs, key)
+        .map_err(|e| invalid(format!("Certificado/chave TLS inválidos: {}", e)))?;
+    server_config.alpn_protocols = alpn_protocols.iter().map(|p| p.as_bytes().to_vec()).collect();
+
+    Ok(TlsListener { acceptor: TlsAcceptor::from(Arc::new(server_config)), client_auth })
+}
+
+// Carrega o par inicial. Sem a seção `tls`, o listener continua em TCP puro.
+pub fn init(config: Option<&TlsConfig>) -> io::Result<()> {
+    let (listener, http_listener) = match config {
+        Some(config) => (Some(Arc::new(build_listener(config)?)), Some(Arc::new(build_http_listener(config)?))),
+        None => (None, None),
+    };
+    *LISTENER.write().unwrap() = listener;
+    *HTTP_LISTENER.write().unwrap() = http_listener;
+    Ok(())
+}
+
+type Signature = (TlsConfig, [Option<(SystemTime, u64)>; 3]);
+
+fn signature_of(config: &TlsConfig) -> Signature {
+    let cert = file_signature(Path::new(&config.cert_file));
+    let key = file_signature(Path::new(&config.key_file));
+    let client_ca = config.client_auth.as_ref().and_then(|client_auth| file_signature(Path::new(&client_auth.ca_file)));
+    (config.clone(), [cert, key, client_ca])
+}
+
+// Vigia certificado, chave, CA de clientes (e a seção `tls` da configuração ativa) e
+// recarrega o listener. Ligar ou desligar o TLS exige reinício; aqui só se troca o material em uso.
+pub async fn watch_certificates(poll_interval: Duration) {
+    let mut last_seen = config::current().tls.as_ref().map(signature_of);
+    let mut ticker = tokio::time::interval(poll_interval);
+
+    loop {
+        ticker.tick().await;
+        if listener().is_none() {
+            continue;
+        }
+        let Some(tls) = config::current().tls.clone() else {
+            continue;
+        };
+
+        let signature = signature_of(&tls);
+        if last_seen.as_ref() == Some(&signature) {
+            continue;
+        }
+        last_seen = Some(signature);
+
+        match build_listener(&tls).and_then(|listener| Ok((listener, build_http_listener(&tls)?))) {
+            Ok((listener, http_listener)) => {
+                *LISTENER.write().unwrap() = Some(Arc::new(listener));
+                *HTTP_LISTENER.write().unwrap() = Some(Arc::new(http_listener));
+                info!("Certificado recarregado de {}.", PathBuf::from(&tls.cert_file).display());
+            }
+            Err(e) => error!("Falha ao recarregar o certificado (mantendo o anterior): {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair, SanType};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::rustls::pki_types::ServerName;
+    use tokio_rustls::rustls::ClientConfig;
+    use tokio_rustls::TlsConnector;
+
+    fn temp_file(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("sygma_tls_{}_{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path.display().to_string()
+    }
+
+    // Gera um certificado autoassinado para `localhost` em arquivos temporários
+    fn self_signed(name: &str) -> (TlsConfig, CertificateDer<'static>) {
+        let generated = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let config = TlsConfig {
+            cert_file: temp_file(&format!("{}.pem", name), &generated.cert.pem()),
+            key_file: temp_file(&format!("{}.key", name), &generated.key_pair.serialize_pem()),
+            alpn_protocols: default_alpn(),
+            client_auth: None,
+        };
+        (config, generated.cert.der().clone())
+    }
+
+    fn ca() -> (rcgen::Certificate, KeyPair) {
+        ca_named("Sygma Devices CA")
+    }
+
+    fn ca_named(name: &str) -> (rcgen::Certificate, KeyPair) {
+        let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.distinguished_name.push(DnType::CommonName, name);
+        let key = KeyPair::generate().unwrap();
+        (params.self_signed(&key).unwrap(), key)
+    }
+
+    // Certificado de dispositivo assinado pelo CA; `expired` gera um já vencido
+    fn device_cert(ca: &(rcgen::Certificate, KeyPair), name: &str, expired: bool) -> (CertificateDer<'static>, KeyPair) {
+        let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        params.distinguished_name.push(DnType::CommonName, name);
+        params.subject_alt_names = vec![SanType::URI(format!("sygma://{}", name).try_into().unwrap())];
+        if expired {
+            params.not_before = rcgen::date_time_ymd(2000, 1, 1);
+            params.not_after = rcgen::date_time_ymd(2001, 1, 1);
+        }
+        let key = KeyPair::generate().unwrap();
+        (params.signed_by(&key, &ca.0, &ca.1).unwrap().der().clone(), key)
+    }
+
+    fn client_auth(ca: &(rcgen::Certificate, KeyPair), identity_from: IdentitySource) -> ClientAuth {
+        let ca_file = temp_file(&format!("ca_{:?}_{}.pem", identity_from, rand_suffix()), &ca.0.pem());
+        ClientAuth::new(&ClientAuthConfig { ca_file, identity_from, token_mode: TokenMode::Alongside }).unwrap()
+    }
+
+    fn rand_suffix() -> u128 {
+        SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_nanos()
+    }
+
+    // Teste 1: Handshake com CA próprio, ALPN negociado e dados trafegando cifrados.
+    #[tokio::test]
+    async fn test_tls_handshake_with_alpn() {
+        let (config, ca) = self_signed("handshake");
+        let listener = build_listener(&config).unwrap();
+
+        let tcp = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = tcp.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = tcp.accept().await.unwrap();
+            let (mut tls, identity) = listener.accept(stream, Duration::from_secs(5)).await.unwrap();
+            assert!(identity.is_none(), "Sem client_auth, não há identidade de certificado.");
+            let mut buffer = [0; 64];
+            let n = tls.read(&mut buffer).await.unwrap();
+            tls.write_all(&buffer[..n]).await.unwrap();
+        });
+
+        let mut roots = RootCertStore::empty();
+        roots.add(ca).unwrap();
+        let mut client_config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        client_config.alpn_protocols = vec![SYGMA_ALPN.as_bytes().to_vec()];
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let server_name = ServerName::try_from("localhost").unwrap();
+        let mut tls = TlsConnector::from(Arc::new(client_config)).connect(server_name, stream).await.unwrap();
+        assert_eq!(tls.get_ref().1.alpn_protocol(), Some(SYGMA_ALPN.as_bytes()));
+
+        tls.write_all(b"PING").await.unwrap();
+        let mut buffer = [0; 4];
+        tls.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"PING");
+    }
+
+    // Teste 2: Chave que não corresponde ao certificado é rejeitada (o par anterior seria mantido).
+    #[test]
+    fn test_mismatched_key_is_rejected() {
+        let (first, _) = self_signed("first");
+        let (second, _) = self_signed("second");
+        assert!(build_listener(&first).is_ok());
+
+        let mismatched = TlsConfig { key_file: second.key_file.clone(), ..first.clone() };
+        assert!(build_listener(&mismatched).is_err());
+        assert!(build_listener(&TlsConfig { cert_file: "/nao/existe.pem".to_string(), ..first }).is_err());
+    }
+
+    // Teste 3: Motivos distintos para certificado ausente, expirado e de CA desconhecido.
+    #[test]
+    fn test_client_certificate_rejection_reasons() {
+        let trusted = ca();
+        let auth = client_auth(&trusted, IdentitySource::CommonName);
+        let now = UnixTime::now();
+
+        let (valid, _) = device_cert(&trusted, "device-1", false);
+        assert_eq!(auth.identify(Some(std::slice::from_ref(&valid)), now), Ok("device-1".to_string()));
+        assert_eq!(auth.identify(None, now), Err(ClientCertError::Missing));
+        assert_eq!(auth.identify(Some(&[]), now), Err(ClientCertError::Missing));
+
+        let (expired, _) = device_cert(&trusted, "device-2", true);
+        assert_eq!(auth.identify(Some(&[expired]), now), Err(ClientCertError::Expired));
+
+        let (foreign, _) = device_cert(&ca_named("Outro CA"), "intruso", false);
+        assert_eq!(auth.identify(Some(&[foreign]), now), Err(ClientCertError::UnknownCa));
+        let (impostor, _) = device_cert(&ca(), "impostor", false);
+        assert_eq!(auth.identify(Some(&[impostor]), now), Err(ClientCertError::UnknownCa), "CA com o mesmo nome, mas outra chave.");
+
+        let by_san = client_auth(&trusted, IdentitySource::San);
+        assert_eq!(by_san.identify(Some(&[valid]), now), Ok("sygma://device-1".to_string()));
+    }
+
+    // Teste 4: Com mTLS, o handshake aceita clientes sem certificado; a rejeição vem depois, com motivo.
+    #[tokio::test]
+    async fn test_mtls_handshake_identity() {
+        let trusted = ca();
+        let (mut config, server_ca) = self_signed("mtls");
+        config.client_auth = Some(ClientAuthConfig {
+            ca_file: temp_file("mtls_ca.pem", &trusted.0.pem()),
+            identity_from: IdentitySource::CommonName,
+            token_mode: TokenMode::Instead,
+        });
+        let listener = Arc::new(build_listener(&config).unwrap());
+
+        let tcp = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = tcp.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let mut identities = Vec::new();
+            for _ in 0..2 {
+                let (stream, _) = tcp.accept().await.unwrap();
+                let (mut tls, identity) = listener.accept(stream, Duration::from_secs(5)).await.unwrap();
+                tls.write_all(b"OK").await.unwrap();
+                identities.push(identity);
+            }
+            identities
+        });
+
+        let mut roots = RootCertStore::empty();
+        roots.add(server_ca).unwrap();
+        let (device, device_key) = device_cert(&trusted, "device-7", false);
+        let builder = || ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots.clone());
+        let with_cert = builder()
+            .with_client_auth_cert(vec![device], PrivateKeyDer::try_from(device_key.serialize_der()).unwrap())
+            .unwrap();
+        let without_cert = builder().with_no_client_auth();
+
+        for client_config in [with_cert, without_cert] {
+            let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let server_name = ServerName::try_from("localhost").unwrap();
+            let mut tls = TlsConnector::from(Arc::new(client_config)).connect(server_name, stream).await.unwrap();
+            let mut buffer = [0; 2];
+            tls.read_exact(&mut buffer).await.unwrap();
+        }
+
+        let identities = server.await.unwrap();
+        assert_eq!(identities[0], Some(Ok("device-7".to_string())));
+        assert_eq!(identities[1], Some(Err(ClientCertError::Missing)));
+    }
+
+    // Teste 5: A API HTTP negocia http/1.1 com clientes HTTP comuns (que não conhecem sygma/1).
+    #[tokio::test]
+    async fn test_http_listener_accepts_http_alpn() {
+        let (config, ca) = self_signed("http_alpn");
+        let listener = build_http_listener(&config).unwrap();
+
+        let tcp = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = tcp.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = tcp.accept().await.unwrap();
+            let (mut tls, _) = listener.accept(stream, Duration::from_secs(5)).await.unwrap();
+            tls.write_all(b"OK").await.unwrap();
+        });
+
+        let mut roots = RootCertStore::empty();
+        roots.add(ca).unwrap();
+        let mut client_config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        client_config.alpn_protocols = vec![HTTP_ALPN.as_bytes().to_vec()];
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let server_name = ServerName::try_from("localhost").unwrap();
+        let mut tls = TlsConnector::from(Arc::new(client_config)).connect(server_name, stream).await.unwrap();
+        assert_eq!(tls.get_ref().1.alpn_protocol(), Some(HTTP_ALPN.as_bytes()));
+        let mut buffer = [0; 2];
+        tls.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"OK");
+    }
+}
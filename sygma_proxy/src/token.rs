@@ -0,0 +1,213 @@
+// sygma_proxy/src/token.rs - Formato e Claims do Token Sygma
+//
+// Formato: AUTH_SYGMA_VALID_<subject>[;chave=valor]...
+// Ex.: AUTH_SYGMA_VALID_conta42;exp=1767225600;tier=gold;scope=retail,payroll
+// Proteção contra replay: `;nonce=<aleatório>;ts=<segundos Unix>`, um par novo por requisição (ver replay.rs).
+// Chaves desconhecidas são ignoradas para manter compatibilidade com clientes futuros.
+// Com `auth.hmac_key` configurada, o token termina em `;sig=<hex>`: HMAC-SHA256 de tudo antes de `;sig=`.
+
+use std::fmt;
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+pub const VALID_TOKEN_PREFIX: &str = "AUTH_SYGMA_VALID_";
+const SIGNATURE_FIELD: &str = ";sig=";
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenClaims {
+    pub subject: String,
+    // Expiração do token em segundos Unix (opcional)
+    pub exp: Option<u64>,
+    // Classe do token (define a faixa de rate limit; opcional)
+    pub tier: Option<String>,
+    // Escopos do token (claim `scope=a,b`), base dos limites por escopo da seção `policy`
+    pub scopes: Vec<String>,
+    // Valor único por requisição e instante de criação em segundos Unix (replay.rs)
+    pub nonce: Option<String>,
+    pub ts: Option<u64>,
+}
+
+impl TokenClaims {
+    // Retorna `None` se o token não tem o prefixo válido ou se algum claim conhecido é malformado.
+    pub fn parse(token: &str) -> Option<Self> {
+        let body = token.strip_prefix(VALID_TOKEN_PREFIX)?;
+        let mut fields = body.split(';');
+        let subject = fields.next().unwrap_or_default().to_string();
+
+        let mut exp = None;
+        let mut tier = None;
+        let mut scopes = Vec::new();
+        let mut nonce = None;
+        let mut ts = None;
+        for field in fields {
+            let (key, value) = field.split_once('=')?;
+            match key {
+                "exp" => exp = Some(value.parse().ok()?),
+                "tier" => tier = Some(value.to_string()),
+                "scope" => scopes = value.split(',').filter(|scope| !scope.is_empty()).map(str::to_string).collect(),
+                "nonce" => nonce = Some(value.to_string()).filter(|nonce| !nonce.is_empty()),
+                "ts" => ts = Some(value.parse().ok()?),
+                _ => {}
+            }
+        }
+
+        Some(TokenClaims { subject, exp, tier, scopes, nonce, ts })
+    }
+
+    pub fn is_expired(&self, now_unix: u64) -> bool {
+        matches!(self.exp, Some(exp) if exp <= now_unix)
+    }
+}
+
+fn mac_for(key: &[u8], message: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC aceita chaves de qualquer tamanho");
+    mac.update(message.as_bytes());
+    mac
+}
+
+// Acrescenta `;sig=<hex>` ao token (o mesmo cálculo feito pelo sygma_client)
+#[cfg(test)]
+pub fn sign(unsigned_token: &str, key: &[u8]) -> String {
+    let signature = mac_for(key, unsigned_token).finalize().into_bytes();
+    format!("{}{}{}", unsigned_token, SIGNATURE_FIELD, hex::encode(signature))
+}
+
+// Chave HMAC do config.yaml nos testes (ver config::initial_config)
+#[cfg(test)]
+pub const TEST_HMAC_KEY: &str = "chave-hmac-dos-testes";
+
+#[cfg(test)]
+pub fn signed_token(unsigned_token: &str) -> String {
+    sign(unsigned_token, TEST_HMAC_KEY.as_bytes())
+}
+
+// Token de uma requisição nos testes: nonce novo, ts atual e assinatura com TEST_HMAC_KEY
+#[cfg(test)]
+pub fn request_token(unsigned_token: &str) -> String {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static NONCE: AtomicU64 = AtomicU64::new(0);
+
+    let nonce = NONCE.fetch_add(1, Ordering::SeqCst);
+    let ts = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    signed_token(&format!("{};nonce={:x};ts={}", unsigned_token, nonce, ts))
+}
+
+// Comparação em tempo constante (via `verify_slice`)
+pub fn verify_signature(token: &str, key: &[u8]) -> bool {
+    let Some((message, signature_hex)) = token.rsplit_once(SIGNATURE_FIELD) else {
+        return false;
+    };
+    let Ok(signature) = hex::decode(signature_hex) else {
+        return false;
+    };
+    mac_for(key, message).verify_slice(&signature).is_ok()
+}
+
+// Chave do TRUST_CACHE e do cache negativo: o token sem `nonce`, `ts` e `sig`, que mudam a cada
+// requisição. Sem isso cada requisição com nonce ocuparia uma entrada usada uma única vez.
+pub fn cache_key(token: &str) -> String {
+    let unsigned = token.rsplit_once(SIGNATURE_FIELD).map_or(token, |(message, _)| message);
+    unsigned.split(';')
+        .filter(|field| !field.starts_with("nonce=") && !field.starts_with("ts="))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+// Forma segura de um token para logs: uma impressão digital curta, suficiente para correlacionar
+// revogações sem expor a credencial. Ex.: token[#9b1d44a2]
+pub struct Redacted<'a>(pub &'a str);
+
+// Token com a assinatura já verificada contra `auth.hmac_key`: só então o subject deixa de ser a
+// própria credencial (AUTH_SYGMA_VALID_<subject>) e pode acompanhar a impressão digital.
+// Ex.: token[conta42#3fa91c0e]
+pub struct RedactedSigned<'a> {
+    pub token: &'a str,
+    pub subject: &'a str,
+}
+
+fn fingerprint(token: &str) -> String {
+    hex::encode(&Sha256::digest(token.as_bytes())[..4])
+}
+
+impl fmt::Display for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "token[#{}]", fingerprint(self.0))
+    }
+}
+
+impl fmt::Display for RedactedSigned<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "token[{}#{}]", self.subject, fingerprint(self.token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Teste 1: Tokens legados (sem claims) continuam válidos.
+    #[test]
+    fn test_parse_legacy_token() {
+        let claims = TokenClaims::parse("AUTH_SYGMA_VALID_12345").unwrap();
+        assert_eq!(claims.subject, "12345");
+        assert_eq!(claims.exp, None);
+        assert_eq!(claims.tier, None);
+        assert!(TokenClaims::parse("FRAUD_ATTEMPT_12345").is_none());
+    }
+
+    // Teste 2: Claim `exp` é lido e validado.
+    #[test]
+    fn test_parse_exp_claim() {
+        let claims = TokenClaims::parse("AUTH_SYGMA_VALID_conta42;exp=1000;v=2;tier=gold;scope=retail,payroll").unwrap();
+        assert_eq!(claims.exp, Some(1000));
+        assert_eq!(claims.tier.as_deref(), Some("gold"));
+        assert_eq!(claims.scopes, ["retail", "payroll"]);
+        assert!(!claims.is_expired(999));
+        assert!(claims.is_expired(1000));
+        assert!(TokenClaims::parse("AUTH_SYGMA_VALID_conta42;exp=amanha").is_none());
+
+        let claims = TokenClaims::parse("AUTH_SYGMA_VALID_conta42;nonce=a1b2;ts=1700000000").unwrap();
+        assert_eq!((claims.nonce.as_deref(), claims.ts), (Some("a1b2"), Some(1_700_000_000)));
+        assert!(TokenClaims::parse("AUTH_SYGMA_VALID_conta42;nonce=a1b2;ts=ontem").is_none());
+    }
+
+    // Teste 3: Assinatura HMAC válida só com a mesma chave e sem adulteração.
+    #[test]
+    fn test_hmac_signature() {
+        let token = sign("AUTH_SYGMA_VALID_conta42;exp=99999999999", b"chave");
+        assert!(verify_signature(&token, b"chave"));
+        assert!(!verify_signature(&token, b"outra-chave"));
+        assert!(!verify_signature(&token.replace("conta42", "conta43"), b"chave"));
+        assert!(!verify_signature("AUTH_SYGMA_VALID_conta42", b"chave"), "Token sem assinatura deve falhar.");
+        assert_eq!(TokenClaims::parse(&token).unwrap().subject, "conta42");
+    }
+
+    // Teste 4: A forma redigida nunca contém a assinatura nem os claims do token.
+    #[test]
+    fn test_redacted_token() {
+        let token = sign("AUTH_SYGMA_VALID_conta42;exp=99999999999", b"chave");
+        let redacted = Redacted(&token).to_string();
+        assert!(redacted.starts_with("token[#") && !redacted.contains("conta42"), "Sem verificação, o subject é a credencial.");
+        assert!(!redacted.contains("sig=") && !redacted.contains("exp="));
+        assert_eq!(redacted, Redacted(&token).to_string(), "A impressão digital é estável.");
+        assert!(Redacted("FRAUD_ATTEMPT_12345").to_string().starts_with("token[#"));
+        // Uma `;sig=` qualquer não basta: sem chave configurada, nada foi verificado
+        assert!(!Redacted("AUTH_SYGMA_VALID_conta42;sig=00").to_string().contains("conta42"));
+
+        let signed = RedactedSigned { token: &token, subject: "conta42" }.to_string();
+        assert_eq!(signed, redacted.replacen("token[", "token[conta42", 1), "Mesma impressão digital, com o subject.");
+    }
+
+    // Teste 5: A chave dos caches é a mesma para todas as requisições do mesmo token.
+    #[test]
+    fn test_cache_key_strips_request_claims() {
+        let first = sign("AUTH_SYGMA_VALID_conta42;exp=99999999999;nonce=a1;ts=1700000000", b"chave");
+        let second = sign("AUTH_SYGMA_VALID_conta42;exp=99999999999;nonce=b2;ts=1700000001", b"chave");
+        assert_eq!(cache_key(&first), "AUTH_SYGMA_VALID_conta42;exp=99999999999");
+        assert_eq!(cache_key(&first), cache_key(&second));
+        assert_eq!(cache_key("AUTH_SYGMA_VALID_12345"), "AUTH_SYGMA_VALID_12345");
+    }
+}
@@ -0,0 +1,211 @@
+// sygma_proxy/src/unix_socket.rs - Endereços `unix:/caminho` (Unix domain sockets)
+//
+// `proxy_address` e os endereços dos backends do Kernel aceitam `unix:/caminho`. Num único
+// aparelho (Termux) isso evita disputar portas de loopback com outros apps e impede que qualquer
+// processo local conecte: o arquivo do socket é criado com a permissão `unix_socket.mode` (0600).
+// As credenciais do cliente (uid) vêm do SO e entram no check de origem: só o usuário do Proxy e
+// os uids de `allowed_uids` passam (403 para os demais, contando para o bloqueio).
+// Nos limites de conexão, no rate limit e no bloqueio por IP, cada uid conta como um endereço
+// próprio (`peer_addr`): um app bloqueado não bloqueia os demais usuários do socket.
+
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+
+const UNIX_PREFIX: &str = "unix:";
+
+// Endereço atribuído a um cliente unix nos limites e bloqueios por IP: o uid dentro de 100::/64,
+// o prefixo de descarte (RFC 6666), que nunca é a origem de uma conexão TCP
+pub fn peer_addr(uid: u32) -> SocketAddr {
+    let ip = Ipv6Addr::new(0x100, 0, 0, 0, 0, 0, (uid >> 16) as u16, uid as u16);
+    SocketAddr::new(IpAddr::V6(ip), 0)
+}
+
+// --- SEÇÃO `unix_socket` DO config.yaml ---
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct UnixSocketConfig {
+    // Permissão do arquivo do socket de `proxy_address` (exige reinício)
+    pub mode: u32,
+    // Uids aceitos além do usuário do Proxy (hot reload)
+    pub allowed_uids: Vec<u32>,
+}
+
+impl Default for UnixSocketConfig {
+    fn default() -> Self {
+        UnixSocketConfig { mode: 0o600, allowed_uids: Vec::new() }
+    }
+}
+
+impl UnixSocketConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.mode & !0o777 != 0 {
+            return Err(format!("unix_socket.mode inválido {:o}: esperado 0o000..0o777", self.mode));
+        }
+        if self.mode & 0o600 != 0o600 {
+            return Err(format!("unix_socket.mode {:o}: o dono precisa de leitura e escrita", self.mode));
+        }
+        Ok(())
+    }
+}
+
+// `unix:/caminho` -> `/caminho`; `None` para host:porta
+pub fn socket_path(address: &str) -> Option<&Path> {
+    address.strip_prefix(UNIX_PREFIX).map(Path::new)
+}
+
+// Conexão TCP ou unix, com TLS opcional por cima
+pub trait Connection: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Connection for T {}
+
+// Credenciais do processo do outro lado do socket, informadas pelo SO (SO_PEERCRED)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerCredentials {
+    pub uid: u32,
+    pub pid: Option<i32>,
+    // Mesmo usuário que criou o socket (o do Proxy)
+    owner: bool,
+}
+
+impl PeerCredentials {
+    pub fn allowed(&self, config: &UnixSocketConfig) -> bool {
+        self.owner || config.allowed_uids.contains(&self.uid)
+    }
+}
+
+impl fmt::Display for PeerCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.pid {
+            Some(pid) => write!(f, "unix:uid={},pid={}", self.uid, pid),
+            None => write!(f, "unix:uid={}", self.uid),
+        }
+    }
+}
+
+// Listener unix que remove o arquivo do socket ao ser descartado
+#[derive(Debug)]
+pub struct UnixSocketListener {
+    listener: UnixListener,
+    path: PathBuf,
+    owner_uid: u32,
+}
+
+impl UnixSocketListener {
+    // Socket antigo substituído e permissão `mode` desde o início (ver sygma_protocol::unix_socket)
+    pub fn bind(path: &Path, mode: u32) -> io::Result<Self> {
+        let listener = UnixListener::from_std(sygma_protocol::unix_socket::bind(path, mode)?)?;
+        let owner_uid = std::fs::metadata(path)?.uid();
+        Ok(UnixSocketListener { listener, path: path.to_path_buf(), owner_uid })
+    }
+
+    pub async fn accept(&self) -> io::Result<(UnixStream, PeerCredentials)> {
+        let (stream, _) = self.listener.accept().await?;
+        let credentials = stream.peer_cred()?;
+        let peer = PeerCredentials { uid: credentials.uid(), pid: credentials.pid(), owner: credentials.uid() == self.owner_uid };
+        Ok((stream, peer))
+    }
+}
+
+impl Drop for UnixSocketListener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+// Listener de `proxy_address`: host:porta ou unix:/caminho
+pub enum ClientListener {
+    Tcp(TcpListener),
+    Unix(UnixSocketListener),
+}
+
+impl ClientListener {
+    pub async fn bind(address: &str, config: &UnixSocketConfig) -> io::Result<Self> {
+        match socket_path(address) {
+            Some(path) => Ok(ClientListener::Unix(UnixSocketListener::bind(path, config.mode)?)),
+            None => Ok(ClientListener::Tcp(TcpListener::bind(address).await?)),
+        }
+    }
+
+    // Clientes unix recebem o `peer_addr` do seu uid e suas credenciais
+    pub async fn accept(&self) -> io::Result<(Box<dyn Connection>, SocketAddr, Option<PeerCredentials>)> {
+        match self {
+            ClientListener::Tcp(listener) => {
+                let (stream, addr) = listener.accept().await?;
+                Ok((Box::new(stream), addr, None))
+            }
+            ClientListener::Unix(listener) => {
+                let (stream, peer) = listener.accept().await?;
+                Ok((Box::new(stream), peer_addr(peer.uid), Some(peer)))
+            }
+        }
+    }
+}
+
+// Conexão de saída (backends do Kernel): host:porta ou unix:/caminho
+pub async fn connect(address: &str) -> io::Result<Box<dyn Connection>> {
+    match socket_path(address) {
+        Some(path) => Ok(Box::new(UnixStream::connect(path).await?)),
+        None => {
+            let stream = TcpStream::connect(address).await?;
+            stream.set_nodelay(true)?;
+            Ok(Box::new(stream))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Teste 1: Endereços unix:, permissão do socket e validação de `mode`.
+    #[test]
+    fn test_addresses_and_mode() {
+        assert_eq!(socket_path("unix:/data/sygma/proxy.sock"), Some(Path::new("/data/sygma/proxy.sock")));
+        assert_eq!(socket_path("127.0.0.1:7878"), None);
+
+        assert!(UnixSocketConfig::default().validate().is_ok());
+        assert!(UnixSocketConfig { mode: 0o660, ..UnixSocketConfig::default() }.validate().is_ok());
+        assert!(UnixSocketConfig { mode: 0o066, ..UnixSocketConfig::default() }.validate().is_err());
+        assert!(UnixSocketConfig { mode: 0o4600, ..UnixSocketConfig::default() }.validate().is_err());
+        let config: UnixSocketConfig = serde_yaml::from_str("mode: 0o660\nallowed_uids: [10123]\n").unwrap();
+        assert_eq!((config.mode, config.allowed_uids), (0o660, vec![10123]));
+    }
+
+    // Teste 2: Socket 0600 removido no descarte; o cliente chega com o uid do processo.
+    #[tokio::test]
+    async fn test_unix_listener_and_peer_credentials() {
+        let path = std::env::temp_dir().join(format!("sygma_unix_test_{}.sock", std::process::id()));
+        let address = format!("unix:{}", path.display());
+        let listener = ClientListener::bind(&address, &UnixSocketConfig::default()).await.unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        let mut client = connect(&address).await.unwrap();
+        let (mut server, addr, peer) = listener.accept().await.unwrap();
+        client.write_all(b"PING").await.unwrap();
+        let mut buffer = [0; 4];
+        server.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"PING");
+
+        let peer = peer.expect("Clientes unix trazem credenciais.");
+        assert_eq!(addr, peer_addr(peer.uid));
+        assert_ne!(peer_addr(peer.uid).ip(), peer_addr(peer.uid + 1).ip(), "Limites e bloqueios são por uid.");
+        assert!(!sygma_protocol::unix_socket::staging_dir(&path).exists(), "O diretório do bind é removido.");
+        assert_eq!(peer.pid, Some(std::process::id() as i32));
+        assert!(peer.allowed(&UnixSocketConfig::default()), "O usuário do Proxy é sempre aceito.");
+        let stranger = PeerCredentials { uid: peer.uid + 1, pid: None, owner: false };
+        assert!(!stranger.allowed(&UnixSocketConfig::default()));
+        assert!(stranger.allowed(&UnixSocketConfig { allowed_uids: vec![peer.uid + 1], ..UnixSocketConfig::default() }));
+
+        drop(listener);
+        assert!(!path.exists(), "O arquivo do socket é removido com o listener.");
+    }
+}
@@ -0,0 +1,11 @@
+// sygma_proxy/src/watch.rs - Detecção de mudanças em arquivos vigiados (por polling)
+
+use std::path::Path;
+use std::time::SystemTime;
+
+// Assinatura usada para detectar mudanças no arquivo (mtime + tamanho).
+// `None` quando o arquivo não existe (a remoção também conta como mudança).
+pub fn file_signature(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}